@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::Pattern,
+    error::{Error, Position},
+    functions::{BuiltInFunction, Type},
+    node::Node,
+    relation::Substitution,
+    scope::Scope,
+    DEFINED_WORDS, KEYWORDS,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Builtin,
+    Function,
+    Parameter,
+    Variable,
+    Keyword,
+}
+
+// A name as it is used at one place in the source.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub position: Position,
+    pub ty: Option<String>,
+    pub definition: Option<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub detail: String,
+}
+
+#[derive(Debug)]
+pub struct Analysis {
+    pub error: Option<Error>,
+    pub symbols: Vec<Symbol>,
+    locals: Vec<(Position, Completion)>,
+    globals: Vec<Completion>,
+}
+
+impl Analysis {
+    pub fn symbol_at(&self, line: usize, column: usize) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .find(|s| contains(&s.position, line, column))
+    }
+
+    pub fn completions(&self, line: usize, column: usize) -> Vec<Completion> {
+        let mut completions = self
+            .locals
+            .iter()
+            .filter(|(range, _)| contains(range, line, column))
+            .map(|(_, completion)| completion.clone())
+            .collect::<Vec<_>>();
+        for completion in &self.globals {
+            if completions.iter().all(|c| c.name != completion.name) {
+                completions.push(completion.clone());
+            }
+        }
+        completions
+    }
+}
+
+fn contains(pos: &Position, line: usize, column: usize) -> bool {
+    (pos.line(), pos.column()) <= (line, column)
+        && (line, column) < (pos.line_end(), pos.column_end())
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    name: String,
+    kind: SymbolKind,
+    position: Position,
+}
+
+struct Walker<'a> {
+    subst: &'a Substitution,
+    // Top-level definitions of imported modules, by their runtime name.
+    modules: HashMap<String, Binding>,
+    scopes: Vec<(Position, Vec<Binding>)>,
+    symbols: Vec<Symbol>,
+    locals: Vec<(Position, Completion)>,
+}
+
+impl Walker<'_> {
+    fn bind(&mut self, name: &str, kind: SymbolKind, position: &Position, ty: Option<&Type>) {
+        let (range, bindings) = self.scopes.last_mut().unwrap();
+        bindings.push(Binding {
+            name: name.to_string(),
+            kind,
+            position: position.clone(),
+        });
+        self.locals.push((
+            range.clone(),
+            Completion {
+                name: name.to_string(),
+                kind,
+                detail: ty.map_or_else(String::new, |ty| self.subst.apply(ty).to_string()),
+            },
+        ));
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            position: position.clone(),
+            ty: ty.map(|ty| self.subst.apply(ty).to_string()),
+            definition: Some(position.clone()),
+        });
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|(_, bindings)| bindings.iter().rev())
+            .find(|b| b.name == name)
+            .or_else(|| self.modules.get(name))
+    }
+
+    fn use_name(&mut self, name: &str, position: &Position, ty: Type, function: bool) {
+        let (kind, definition) = match self.lookup(name) {
+            Some(binding) => (binding.kind, Some(binding.position.clone())),
+            None if DEFINED_WORDS.iter().any(|f| f.name() == name) => (SymbolKind::Builtin, None),
+            None if function => (SymbolKind::Function, None),
+            None => (SymbolKind::Variable, None),
+        };
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            position: position.clone(),
+            ty: Some(self.subst.apply(&ty).to_string()),
+            // Definitions in the bundled libraries can't be opened.
+            definition: definition.filter(|pos| !pos.file().starts_with('<')),
+        });
+    }
+
+    fn block(&mut self, range: &Position, nodes: &[Node]) {
+        self.scopes.push((range.clone(), Vec::new()));
+        for node in nodes {
+            if let Node::Define(name, value) = node {
+                let kind = match **value {
+                    Node::Function(..) => SymbolKind::Function,
+                    _ => SymbolKind::Variable,
+                };
+                self.bind(
+                    &name.to_string(),
+                    kind,
+                    name.position(),
+                    Some(&value.get_type()),
+                );
+            }
+        }
+        for node in nodes {
+            self.node(node);
+        }
+        self.scopes.pop();
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::Number(_) | Node::Str(_) | Node::Bool(_) => {}
+            Node::TypeDef(..) | Node::Import(..) => {}
+            Node::Statements(nodes, _, pos) => self.block(pos, nodes),
+            Node::Call(name, args, ret, _) => {
+                let ty = match args.len() {
+                    0 => ret.clone(),
+                    _ => Type::Function(
+                        args.iter().map(|a| a.get_type()).collect(),
+                        Box::new(ret.clone()),
+                    ),
+                };
+                self.use_name(&name.to_string(), name.position(), ty, true);
+                args.iter().for_each(|a| self.node(a));
+            }
+            Node::FuncAccess(name, params, ret) => {
+                let ty = Type::Function(params.clone(), Box::new(ret.clone()));
+                self.use_name(&name.to_string(), name.position(), ty, true);
+            }
+            Node::Var(name, ty) => {
+                self.use_name(&name.to_string(), name.position(), ty.clone(), false)
+            }
+            Node::Define(_, value) => self.node(value),
+            Node::Let(name, value, ty, _) => {
+                self.node(value);
+                self.bind(
+                    &name.to_string(),
+                    SymbolKind::Variable,
+                    name.position(),
+                    Some(ty),
+                );
+            }
+            Node::Assign(name, value) => {
+                self.use_name(&name.to_string(), name.position(), value.get_type(), false);
+                self.node(value);
+            }
+            Node::Function(f, pos) => {
+                self.scopes.push((pos.clone(), Vec::new()));
+                for (ty, param) in f.params() {
+                    self.bind(
+                        &param.to_string(),
+                        SymbolKind::Parameter,
+                        param.position(),
+                        Some(ty),
+                    );
+                }
+                self.block(pos, f.body());
+                self.scopes.pop();
+            }
+            Node::While(condition, body, _) => {
+                self.node(condition);
+                self.node(body);
+            }
+            Node::For(var, start, end, body, pos) => {
+                self.node(start);
+                self.node(end);
+                self.scopes.push((pos.clone(), Vec::new()));
+                self.bind(
+                    &var.to_string(),
+                    SymbolKind::Variable,
+                    var.position(),
+                    Some(&Type::Number),
+                );
+                self.node(body);
+                self.scopes.pop();
+            }
+            Node::If(condition, then, else_, ..) => {
+                self.node(condition);
+                self.node(then);
+                self.node(else_);
+            }
+            Node::Record(_, fields, _) => fields.iter().for_each(|(_, value)| self.node(value)),
+            Node::Field(_, record, _) => self.node(record),
+            Node::Update(record, fields, ..) => {
+                self.node(record);
+                fields.iter().for_each(|(_, value)| self.node(value));
+            }
+            Node::Variant(_, args, _) => args.iter().for_each(|a| self.node(a)),
+            Node::Match(target, arms, ..) => {
+                self.node(target);
+                for (pattern, body) in arms {
+                    self.scopes.push((body.position().clone(), Vec::new()));
+                    if let Pattern::Variant(_, binders) = pattern {
+                        for binder in binders {
+                            self.bind(
+                                &binder.to_string(),
+                                SymbolKind::Variable,
+                                binder.position(),
+                                None,
+                            );
+                        }
+                    }
+                    self.node(body);
+                    self.scopes.pop();
+                }
+            }
+            Node::Try(body, fallback, ..) => {
+                self.node(body);
+                if let Some(fallback) = fallback {
+                    self.node(fallback);
+                }
+            }
+        }
+    }
+}
+
+// `ast` holds what was checked before `error`, if anything was.
+pub fn walk<'a>(
+    ast: Option<Node>,
+    error: Option<Error>,
+    scope: &Scope,
+    subst: &Substitution,
+    modules: impl Iterator<Item = &'a Node>,
+    builtins: impl Iterator<Item = &'static BuiltInFunction>,
+) -> Analysis {
+    let mut globals = scope
+        .names()
+        .into_iter()
+        .map(|(name, ty)| Completion {
+            name: name.to_string(),
+            kind: match ty {
+                Type::Function(..) => SymbolKind::Function,
+                _ => SymbolKind::Variable,
+            },
+            detail: subst.apply(ty).to_string(),
+        })
+        .collect::<Vec<_>>();
+    globals.extend(builtins.map(|f| Completion {
+        name: f.name().to_string(),
+        kind: SymbolKind::Builtin,
+        detail: Type::Function(f.params().to_vec(), Box::new(f.ret().clone())).to_string(),
+    }));
+    globals.extend(KEYWORDS.iter().map(|k| Completion {
+        name: k.to_string(),
+        kind: SymbolKind::Keyword,
+        detail: String::new(),
+    }));
+    let mut walker = Walker {
+        subst,
+        modules: HashMap::new(),
+        scopes: Vec::new(),
+        symbols: Vec::new(),
+        locals: Vec::new(),
+    };
+    for node in modules {
+        if let Node::Define(name, value) | Node::Let(name, value, ..) = node {
+            let kind = match **value {
+                Node::Function(..) => SymbolKind::Function,
+                _ => SymbolKind::Variable,
+            };
+            walker.modules.insert(
+                name.to_string(),
+                Binding {
+                    name: name.to_string(),
+                    kind,
+                    position: name.position().clone(),
+                },
+            );
+        }
+    }
+    if let Some(ast) = ast {
+        walker.node(&ast);
+    }
+    Analysis {
+        error,
+        symbols: walker.symbols,
+        locals: walker.locals,
+        globals,
+    }
+}
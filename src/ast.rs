@@ -0,0 +1,284 @@
+use std::fmt;
+
+use crate::{error::Position, token::Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExpr {
+    Named(Token, Vec<TypeExpr>),
+    Hole(Position),
+    Function(Vec<TypeExpr>, Box<TypeExpr>, Position),
+}
+
+impl TypeExpr {
+    pub fn position(&self) -> &Position {
+        match self {
+            TypeExpr::Named(t, _) => t.position(),
+            TypeExpr::Hole(pos) | TypeExpr::Function(.., pos) => pos,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match self {
+            TypeExpr::Named(_, args) => args.iter().all(|a| a.is_complete()),
+            TypeExpr::Hole(_) => false,
+            TypeExpr::Function(params, ret, _) => {
+                params.iter().all(|p| p.is_complete()) && ret.is_complete()
+            }
+        }
+    }
+}
+
+// Applications are kept flat, as a sequence of `Expr`s, because how many
+// arguments a word takes is only known once names have been resolved.
+// `Define`, `Let`, `Assign`, `Private`, `Test`, `If`, `Field`, `With`, `Try`
+// and `Else` likewise take the expressions that
+// follow them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Variant(Token, Vec<Token>),
+    Wildcard(Token),
+}
+
+impl Pattern {
+    pub fn position(&self) -> &Position {
+        match self {
+            Pattern::Variant(t, _) | Pattern::Wildcard(t) => t.position(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(Token),
+    Str(Token),
+    Bool(Token),
+    Word(Token),
+    FuncAccess(Token),
+    Define(Token),
+    If(Token),
+    Block(Vec<Expr>, Position),
+    Function(
+        Vec<(TypeExpr, Token)>,
+        Option<TypeExpr>,
+        Vec<Expr>,
+        Position,
+    ),
+    Record(Token, Vec<(TypeExpr, Token)>, Position),
+    Construct(Token, Vec<(Token, Vec<Expr>)>, Position),
+    Field(Token),
+    With(Token),
+    Fields(Vec<(Token, Vec<Expr>)>, Position),
+    Enum(Token, Vec<Token>, Vec<(Token, Vec<TypeExpr>)>, Position),
+    Match(Vec<Expr>, Vec<(Pattern, Vec<Expr>)>, Position),
+    Try(Token),
+    Else(Token),
+    Let(Token, Option<TypeExpr>, bool),
+    Assign(Token),
+    While(Vec<Expr>, Vec<Expr>, Position),
+    For(Token, Vec<Expr>, Vec<Expr>, Position),
+    Import(Token, Option<Token>, Position),
+    Private(Token),
+    Test(Token),
+}
+
+impl Expr {
+    pub fn position(&self) -> &Position {
+        match self {
+            Expr::Number(t)
+            | Expr::Str(t)
+            | Expr::Bool(t)
+            | Expr::Word(t)
+            | Expr::FuncAccess(t)
+            | Expr::Define(t)
+            | Expr::If(t)
+            | Expr::Field(t)
+            | Expr::With(t)
+            | Expr::Try(t)
+            | Expr::Else(t)
+            | Expr::Let(t, ..)
+            | Expr::Assign(t)
+            | Expr::Private(t)
+            | Expr::Test(t) => t.position(),
+            Expr::Block(_, pos)
+            | Expr::Function(.., pos)
+            | Expr::Record(.., pos)
+            | Expr::Construct(.., pos)
+            | Expr::Fields(_, pos)
+            | Expr::Enum(.., pos)
+            | Expr::Match(.., pos)
+            | Expr::While(.., pos)
+            | Expr::For(.., pos)
+            | Expr::Import(.., pos) => pos,
+        }
+    }
+}
+
+impl fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeExpr::Named(t, args) => {
+                write!(f, "{}", t)?;
+                for arg in args {
+                    write!(f, " [{}]", arg)?;
+                }
+                Ok(())
+            }
+            TypeExpr::Hole(_) => Ok(()),
+            TypeExpr::Function(params, ret, _) => write!(
+                f,
+                "{}~> {}",
+                params
+                    .iter()
+                    .map(|p| format!("[{}] ", p))
+                    .collect::<String>(),
+                ret
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Variant(t, binders) => {
+                write!(f, "{}", t)?;
+                for binder in binders {
+                    write!(f, " {}", binder)?;
+                }
+                Ok(())
+            }
+            Pattern::Wildcard(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+fn fields(f: &mut fmt::Formatter, fields: &[(Token, Vec<Expr>)]) -> fmt::Result {
+    write!(f, "{{")?;
+    for (name, exprs) in fields {
+        write!(f, " {} :", name)?;
+        for expr in exprs {
+            write!(f, " {}", expr)?;
+        }
+    }
+    write!(f, " }}")
+}
+
+fn block(f: &mut fmt::Formatter, body: &[Expr]) -> fmt::Result {
+    write!(f, " {{")?;
+    for expr in body {
+        write!(f, " {}", expr)?;
+    }
+    write!(f, " }}")
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Number(t) | Expr::Str(t) | Expr::Bool(t) | Expr::Word(t) => {
+                write!(f, "{}", t)
+            }
+            Expr::FuncAccess(t) => write!(f, "{}!", t),
+            Expr::Define(t) => write!(f, "{} :", t),
+            Expr::Let(t, type_, mutable) => {
+                write!(f, "let ")?;
+                if *mutable {
+                    write!(f, "mut ")?;
+                }
+                if let Some(type_) = type_ {
+                    write!(f, "[{}] ", type_)?;
+                }
+                write!(f, "{} :", t)
+            }
+            Expr::Assign(t) => write!(f, "{} <-", t),
+            Expr::If(_) => write!(f, "?"),
+            Expr::Private(_) => write!(f, "private"),
+            Expr::Test(_) => write!(f, "test"),
+            Expr::Import(path, Some(name), _) => write!(f, "import {} as {}", path, name),
+            Expr::Import(path, None, _) => write!(f, "import {}", path),
+            Expr::While(condition, body, _) => {
+                write!(f, "while")?;
+                for expr in condition {
+                    write!(f, " {}", expr)?;
+                }
+                block(f, body)
+            }
+            Expr::For(var, range, body, _) => {
+                write!(f, "for {} in", var)?;
+                for expr in range {
+                    write!(f, " {}", expr)?;
+                }
+                block(f, body)
+            }
+            Expr::Field(t) | Expr::With(t) | Expr::Try(t) | Expr::Else(t) => write!(f, "{}", t),
+            Expr::Record(name, fields, _) => {
+                write!(f, "record {} {{", name)?;
+                for (t, field) in fields {
+                    write!(f, " [{}] {}", t, field)?;
+                }
+                write!(f, " }}")
+            }
+            Expr::Construct(name, values, _) => {
+                write!(f, "{} ", name)?;
+                fields(f, values)
+            }
+            Expr::Fields(values, _) => fields(f, values),
+            Expr::Enum(name, params, variants, _) => {
+                write!(f, "enum {}", name)?;
+                for param in params {
+                    write!(f, " {}", param)?;
+                }
+                write!(f, " {{")?;
+                for (variant, types) in variants {
+                    write!(f, " {}", variant)?;
+                    for t in types {
+                        write!(f, " [{}]", t)?;
+                    }
+                }
+                write!(f, " }}")
+            }
+            Expr::Match(target, arms, _) => {
+                write!(f, "match")?;
+                for expr in target {
+                    write!(f, " {}", expr)?;
+                }
+                write!(f, " {{")?;
+                for (pattern, body) in arms {
+                    write!(f, " {} ~>", pattern)?;
+                    for expr in body {
+                        write!(f, " {}", expr)?;
+                    }
+                }
+                write!(f, " }}")
+            }
+            Expr::Block(exprs, _) => write!(
+                f,
+                "| {} |",
+                exprs
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            Expr::Function(params, ret, body, _) => {
+                for (t, p) in params {
+                    write!(f, "[{}] {} ", t, p)?;
+                }
+                if let Some(ret) = ret {
+                    if params.is_empty() {
+                        write!(f, "[{}] ", ret)?;
+                    } else {
+                        write!(f, "~> [{}] ", ret)?;
+                    }
+                }
+                write!(
+                    f,
+                    "| {} |",
+                    body.iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,1371 @@
+use std::{borrow::Cow, path::Path, rc::Rc};
+
+use crate::{
+    ast::{Expr, Pattern, TypeExpr},
+    config::Capability,
+    error::{Error, ErrorType, Position},
+    functions::{Type, TypeDef, UserDefinedFunction},
+    lexer,
+    loader::{Loader, Module},
+    node::Node,
+    parser,
+    relation::Substitution,
+    scope::{Export, Scope},
+    token::{Token, TokenType},
+};
+
+type CheckResult = Result<Node, Error>;
+
+struct Cursor<'a> {
+    exprs: &'a [Expr],
+    index: usize,
+    end: &'a Position,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(exprs: &'a [Expr], end: &'a Position) -> Cursor<'a> {
+        Cursor {
+            exprs,
+            index: 0,
+            end,
+        }
+    }
+
+    fn next(&mut self) -> Result<&'a Expr, Error> {
+        match self.exprs.get(self.index) {
+            Some(expr) => {
+                self.index += 1;
+                Ok(expr)
+            }
+            None => Err(Error::new(
+                ErrorType::SyntaxError,
+                self.end.clone(),
+                "Expected an expression".to_string(),
+            )),
+        }
+    }
+
+    fn peek(&self) -> Option<&'a Expr> {
+        self.exprs.get(self.index)
+    }
+
+    fn is_done(&self) -> bool {
+        self.index >= self.exprs.len()
+    }
+}
+
+struct Checker<'a> {
+    subst: Substitution,
+    loader: &'a mut Loader,
+    capabilities: &'a [Capability],
+    depth: usize,
+}
+
+impl Checker<'_> {
+    fn make_type(&mut self, type_: &TypeExpr, scope: &Scope) -> Result<Type, Error> {
+        match type_ {
+            TypeExpr::Named(token, args) => match **token {
+                TokenType::Keyword(ref s) => match s.as_str() {
+                    "Int" => Ok(Type::Number),
+                    "Bool" => Ok(Type::Bool),
+                    "Str" => Ok(Type::Str),
+                    "Any" => Ok(Type::Any),
+                    _ => Err(Error::new(
+                        ErrorType::SyntaxError,
+                        token.position().clone(),
+                        format!("Expected type, found {}", s),
+                    )),
+                },
+                TokenType::Word(ref s) => {
+                    let expected = match scope.find_type(s) {
+                        Some(def) => def.params().len(),
+                        None => 0,
+                    };
+                    if args.len() != expected {
+                        return Err(Error::new(
+                            ErrorType::TypeError,
+                            token.position().clone(),
+                            format!(
+                                "{} takes {} type arguments, but got {}",
+                                s,
+                                expected,
+                                args.len()
+                            ),
+                        ));
+                    }
+                    let args = args
+                        .iter()
+                        .map(|a| self.make_type(a, scope))
+                        .collect::<Result<_, _>>()?;
+                    Ok(match scope.find_type(s) {
+                        Some(_) => Type::Named(Cow::Owned(s.clone()), Cow::Owned(args)),
+                        None => Type::Var(Cow::Owned(s.clone())),
+                    })
+                }
+                _ => Err(Error::new(
+                    ErrorType::SyntaxError,
+                    token.position().clone(),
+                    "Expected parameter type".to_string(),
+                )),
+            },
+            TypeExpr::Hole(_) => Ok(self.subst.fresh()),
+            TypeExpr::Function(params, ret, _) => Ok(Type::Function(
+                params
+                    .iter()
+                    .map(|p| self.make_type(p, scope))
+                    .collect::<Result<_, _>>()?,
+                Box::new(self.make_type(ret, scope)?),
+            )),
+        }
+    }
+
+    fn signature(
+        &mut self,
+        params: &[(TypeExpr, Token)],
+        ret: &Option<TypeExpr>,
+        scope: &Scope,
+    ) -> Result<(Vec<Type>, Type), Error> {
+        let params = params
+            .iter()
+            .map(|(t, _)| self.make_type(t, scope))
+            .collect::<Result<_, _>>()?;
+        let ret = match ret {
+            Some(t) => self.make_type(t, scope)?,
+            None => self.subst.fresh(),
+        };
+        Ok((params, ret))
+    }
+
+    // Record and enum types are visible in the whole block they are declared in.
+    // Functions whose signature is fully written out can also be referenced
+    // before their definition, as long as it is the first definition of
+    // that name in the block.
+    fn declare(&mut self, exprs: &[Expr], scope: &mut Scope) -> Result<(), Error> {
+        for expr in exprs {
+            match expr {
+                Expr::Record(name, ..) => scope.define_type(name.clone(), TypeDef::Record(vec![])),
+                Expr::Enum(name, params, ..) => scope.define_type(
+                    name.clone(),
+                    TypeDef::Enum(params.iter().map(|p| p.to_string()).collect(), vec![]),
+                ),
+                _ => {}
+            }
+        }
+        for expr in exprs {
+            if let Expr::Enum(name, params, variants, _) = expr {
+                let mut checked: Vec<(Token, Vec<Type>)> = vec![];
+                for (variant, types) in variants {
+                    if checked.iter().any(|(v, _)| v == variant) {
+                        return Err(Error::new(
+                            ErrorType::TypeError,
+                            variant.position().clone(),
+                            format!("Variant {} is declared twice in {}", variant, name),
+                        ));
+                    }
+                    let mut payload = vec![];
+                    for t in types {
+                        match self.make_type(t, scope)? {
+                            Type::Var(v) if !params.iter().any(|p| **p == &*v) => {
+                                return Err(Error::new(
+                                    ErrorType::TypeError,
+                                    t.position().clone(),
+                                    format!("Undefined type : {}", v),
+                                ))
+                            }
+                            t => payload.push(t),
+                        }
+                    }
+                    checked.push((variant.clone(), payload));
+                }
+                scope.define_type(
+                    name.clone(),
+                    TypeDef::Enum(params.iter().map(|p| p.to_string()).collect(), checked),
+                );
+            }
+        }
+        for expr in exprs {
+            if let Expr::Record(name, fields, _) = expr {
+                let mut types = vec![];
+                for (t, field) in fields {
+                    if types.iter().any(|(_, f)| f == field) {
+                        return Err(Error::new(
+                            ErrorType::TypeError,
+                            field.position().clone(),
+                            format!("Field {} is declared twice in {}", field, name),
+                        ));
+                    }
+                    match self.make_type(t, scope)? {
+                        Type::Var(v) => {
+                            return Err(Error::new(
+                                ErrorType::TypeError,
+                                t.position().clone(),
+                                format!("Undefined type : {}", v),
+                            ))
+                        }
+                        t => types.push((t, field.clone())),
+                    }
+                }
+                scope.define_type(name.clone(), TypeDef::Record(types));
+            }
+        }
+        let mut seen = vec![];
+        for pair in exprs.windows(2) {
+            let name = match &pair[0] {
+                Expr::Define(name) if !seen.contains(&name) => name,
+                _ => continue,
+            };
+            seen.push(name);
+            if let Expr::Function(params, Some(ret), ..) = &pair[1] {
+                if params.iter().all(|(t, _)| t.is_complete()) && ret.is_complete() {
+                    let (params, ret) = self.signature(params, &Some(ret.clone()), scope)?;
+                    scope.declare((name.clone(), Type::Function(params, Box::new(ret))));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn top_level(
+        &mut self,
+        exprs: &[Expr],
+        end: &Position,
+        scope: &mut Scope,
+    ) -> Result<Vec<Node>, Error> {
+        self.declare(exprs, scope)?;
+        let mut cursor = Cursor::new(exprs, end);
+        let mut statements = Vec::new();
+        while !cursor.is_done() {
+            statements.push(self.expression(&mut cursor, scope)?);
+        }
+        Ok(statements)
+    }
+
+    fn statements(
+        &mut self,
+        exprs: &[Expr],
+        end: &Position,
+        scope: &mut Scope,
+    ) -> Result<(Vec<Node>, Type), Error> {
+        self.depth += 1;
+        let result = self.block(exprs, end, scope);
+        self.depth -= 1;
+        result
+    }
+
+    fn block(
+        &mut self,
+        exprs: &[Expr],
+        end: &Position,
+        scope: &mut Scope,
+    ) -> Result<(Vec<Node>, Type), Error> {
+        match self.prefix(exprs, end, scope) {
+            (statements, None) => {
+                let ret = statements.last().map_or(Type::None, |s| s.get_type());
+                Ok((statements, ret))
+            }
+            (_, Some(err)) => Err(err),
+        }
+    }
+
+    // The statements of a block that check before the first error, and that
+    // error.
+    fn prefix(
+        &mut self,
+        exprs: &[Expr],
+        end: &Position,
+        scope: &mut Scope,
+    ) -> (Vec<Node>, Option<Error>) {
+        let mut new = scope.get_new();
+        let mut statements = Vec::new();
+        let error = self
+            .declare(exprs, &mut new)
+            .and_then(|_| {
+                let mut cursor = Cursor::new(exprs, end);
+                while !cursor.is_done() {
+                    statements.push(self.expression(&mut cursor, &mut new)?);
+                }
+                Ok(())
+            })
+            .err();
+        scope.add(new);
+        (statements, error)
+    }
+
+    fn expression(&mut self, cursor: &mut Cursor, scope: &mut Scope) -> CheckResult {
+        match cursor.next()? {
+            Expr::Number(token) => Ok(Node::Number(token.clone())),
+            Expr::Str(token) => Ok(Node::Str(token.clone())),
+            Expr::Bool(token) => Ok(Node::Bool(token.clone())),
+            Expr::Define(token) => {
+                let node = match cursor.peek() {
+                    Some(Expr::Function(params, ret, body, pos)) => {
+                        cursor.next()?;
+                        let runtime = scope.runtime(token);
+                        let node = self.function(
+                            params,
+                            ret,
+                            body,
+                            pos,
+                            scope,
+                            Some((token, runtime.clone())),
+                        )?;
+                        scope.define_generic((token.clone(), node.get_type()), runtime);
+                        node
+                    }
+                    _ => {
+                        let node = self.expression(cursor, scope)?;
+                        scope.define((token.clone(), self.subst.apply(&node.get_type())));
+                        node
+                    }
+                };
+                Ok(Node::Define(scope.resolve(token), Box::new(node)))
+            }
+            Expr::Assign(token) => {
+                let ty = self.find(scope, token)?;
+                if !scope.is_mutable(token) {
+                    return Err(Error::new(
+                        ErrorType::TypeError,
+                        token.position().clone(),
+                        format!("Cannot assign to {}, it is not mutable", token),
+                    ));
+                }
+                let node = self.expression(cursor, scope)?;
+                if !self.subst.unify(&ty, &node.get_type()) {
+                    return Err(Error::new(
+                        ErrorType::TypeError,
+                        node.position().clone(),
+                        format!(
+                            "Expected type {}, but got {}",
+                            self.subst.apply(&ty),
+                            self.subst.apply(&node.get_type())
+                        ),
+                    ));
+                }
+                Ok(Node::Assign(scope.resolve(token), Box::new(node)))
+            }
+            Expr::Import(path, name, pos) => {
+                let file = match **path {
+                    TokenType::Str(ref file) => file.clone(),
+                    _ => unreachable!(),
+                };
+                let resolved = self.loader.resolve(&file, pos, self.capabilities)?;
+                let module = match self.loader.find(&resolved) {
+                    Some(module) => module,
+                    None => self.load(&resolved, pos)?,
+                };
+                let namespace = match name {
+                    Some(name) => name.to_string(),
+                    None => Path::new(&file)
+                        .file_stem()
+                        .map_or_else(String::new, |s| s.to_string_lossy().into_owned()),
+                };
+                for export in module.exports() {
+                    scope.import(
+                        path.renamed(format!("{}.{}", namespace, export.name)),
+                        export,
+                        &file,
+                    );
+                }
+                for (name, def) in module.types().iter().rev() {
+                    if scope.find_type_token(&name.to_string()).is_none() {
+                        scope.define_type(name.clone(), def.clone());
+                    }
+                }
+                Ok(Node::Import(path.clone(), pos.clone()))
+            }
+            Expr::Private(token) => {
+                let name = match cursor.peek() {
+                    Some(Expr::Define(name)) | Some(Expr::Let(name, ..)) => name,
+                    _ => {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            token.position().clone(),
+                            "Expected a definition after private".to_string(),
+                        ))
+                    }
+                };
+                let node = self.expression(cursor, scope)?;
+                scope.make_private(name);
+                Ok(node)
+            }
+            Expr::Test(token) => {
+                if !matches!(cursor.peek(), Some(Expr::Define(_))) {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        token.position().clone(),
+                        "Expected a function after test".to_string(),
+                    ));
+                }
+                if self.depth > 0 {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        token.position().clone(),
+                        "Tests can only be defined at the top level".to_string(),
+                    ));
+                }
+                let node = self.expression(cursor, scope)?;
+                match &node {
+                    Node::Define(name, func) => match &**func {
+                        Node::Function(f, _) if f.params().is_empty() => {
+                            if let Some(first) = scope
+                                .tests()
+                                .iter()
+                                .find(|t| t.to_string() == name.to_string())
+                            {
+                                return Err(Error::new(
+                                    ErrorType::SyntaxError,
+                                    name.position().clone(),
+                                    format!(
+                                        "Test {} is already defined at {}",
+                                        name,
+                                        first.position()
+                                    ),
+                                ));
+                            }
+                            scope.add_test(name.clone())
+                        }
+                        Node::Function(_, pos) => {
+                            return Err(Error::new(
+                                ErrorType::TypeError,
+                                pos.clone(),
+                                format!("Test {} can't take parameters", name),
+                            ))
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                token.position().clone(),
+                                "Expected a function after test".to_string(),
+                            ))
+                        }
+                    },
+                    _ => unreachable!(),
+                }
+                Ok(node)
+            }
+            Expr::Let(token, type_, mutable) => {
+                let node = self.expression(cursor, scope)?;
+                let ty = match type_ {
+                    Some(type_) => {
+                        let expected = self.make_type(type_, scope)?;
+                        if !self.subst.is_assignable(&expected, &node.get_type()) {
+                            return Err(Error::new(
+                                ErrorType::TypeError,
+                                node.position().clone(),
+                                format!(
+                                    "Expected type {}, but got {}",
+                                    self.subst.apply(&expected),
+                                    self.subst.apply(&node.get_type())
+                                ),
+                            ));
+                        }
+                        expected
+                    }
+                    None => node.get_type(),
+                };
+                let ty = self.subst.apply(&ty);
+                if *mutable {
+                    scope.define_mutable((token.clone(), ty.clone()));
+                } else {
+                    scope.define((token.clone(), ty.clone()));
+                }
+                Ok(Node::Let(
+                    scope.resolve(token),
+                    Box::new(node),
+                    ty,
+                    *mutable,
+                ))
+            }
+            Expr::Word(token)
+                if scope.find(token).is_none() && scope.find_variant(token).is_some() =>
+            {
+                let (name, def) = scope.find_variant(token).unwrap();
+                let (params, payload) = match def {
+                    TypeDef::Enum(params, variants) => (
+                        params,
+                        variants.iter().find(|(v, _)| v == token).unwrap().1.clone(),
+                    ),
+                    TypeDef::Record(_) => unreachable!(),
+                };
+                let ret = Type::Named(
+                    Cow::Owned(name.to_string()),
+                    params
+                        .iter()
+                        .map(|p| Type::Var(Cow::Owned(p.clone())))
+                        .collect(),
+                );
+                let (payload, ret) = match self
+                    .subst
+                    .instantiate(&Type::Function(payload, Box::new(ret)))
+                {
+                    Type::Function(payload, ret) => (payload, ret),
+                    _ => unreachable!(),
+                };
+                let args = self.arguments(token, payload, cursor, scope)?;
+                Ok(Node::Variant(token.clone(), args, self.subst.apply(&ret)))
+            }
+            Expr::Word(token) => match self.find(scope, token)? {
+                Type::Function(params, ret) => {
+                    let args = self.arguments(token, params, cursor, scope)?;
+                    Ok(Node::Call(
+                        scope.resolve(token),
+                        args,
+                        self.subst.apply(&ret),
+                        token.to_string(),
+                    ))
+                }
+                t => Ok(Node::Var(scope.resolve(token), t)),
+            },
+            Expr::FuncAccess(token) => match self.find(scope, token)? {
+                Type::Function(params, ret) => {
+                    Ok(Node::FuncAccess(scope.resolve(token), params, *ret))
+                }
+                t => Err(Error::new(
+                    ErrorType::TypeError,
+                    token.position().clone(),
+                    format!("Expected a function, but {} is {}", token, t),
+                )),
+            },
+            Expr::Function(params, ret, body, pos) => {
+                self.function(params, ret, body, pos, scope, None)
+            }
+            Expr::Block(exprs, pos) => {
+                let (statements, ret) = self.statements(exprs, pos, scope)?;
+                Ok(Node::Statements(statements, ret, pos.clone()))
+            }
+            Expr::Record(name, _, pos) | Expr::Enum(name, _, _, pos) => {
+                Ok(Node::TypeDef(name.clone(), pos.clone()))
+            }
+            Expr::Match(target, arms, pos) => self.matching(target, arms, pos, scope),
+            Expr::Construct(name, values, pos) => {
+                let fields = match scope.find_type(&name.to_string()) {
+                    Some(TypeDef::Record(fields)) => fields.clone(),
+                    _ => {
+                        return Err(Error::new(
+                            ErrorType::UndefinedFunction,
+                            name.position().clone(),
+                            format!("Undefined record type : {}", name),
+                        ))
+                    }
+                };
+                let mut values = self.field_values(name, &fields, values, scope)?;
+                let mut ordered = vec![];
+                for (_, field) in &fields {
+                    match values.iter().position(|(f, _)| f == field) {
+                        Some(i) => ordered.push(values.remove(i)),
+                        None => {
+                            return Err(Error::new(
+                                ErrorType::TypeError,
+                                pos.clone(),
+                                format!("Missing field {} of {}", field, name),
+                            ))
+                        }
+                    }
+                }
+                Ok(Node::Record(name.clone(), ordered, pos.clone()))
+            }
+            Expr::Field(field) => {
+                let target = self.expression(cursor, scope)?;
+                let name = match **field {
+                    TokenType::Field(ref name) => name.as_str(),
+                    _ => unreachable!(),
+                };
+                let (record, fields) = self.record_type(&target, name, scope)?;
+                match fields.iter().find(|(_, f)| **f == name) {
+                    Some((t, _)) => Ok(Node::Field(field.clone(), Box::new(target), t.clone())),
+                    None => Err(Error::new(
+                        ErrorType::TypeError,
+                        field.position().clone(),
+                        format!("{} has no field {}", record, name),
+                    )),
+                }
+            }
+            Expr::With(token) => {
+                let target = self.expression(cursor, scope)?;
+                let (values, pos) = match cursor.next()? {
+                    Expr::Fields(values, pos) => (values, pos),
+                    expr => {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            expr.position().clone(),
+                            format!("Expected '{{' after the target of {}", token),
+                        ))
+                    }
+                };
+                let field = match values.first() {
+                    Some((f, _)) => f.to_string(),
+                    None => String::new(),
+                };
+                let (record, fields) = self.record_type(&target, &field, scope)?;
+                let values = self.field_values(&record, &fields, values, scope)?;
+                let ty = self.subst.apply(&target.get_type());
+                let mut s = token.position().clone();
+                s.merge(pos);
+                Ok(Node::Update(Box::new(target), values, ty, s))
+            }
+            Expr::Fields(_, pos) => Err(Error::new(
+                ErrorType::SyntaxError,
+                pos.clone(),
+                "Unexpected '{'".to_string(),
+            )),
+            Expr::Else(token) => Err(Error::new(
+                ErrorType::SyntaxError,
+                token.position().clone(),
+                "Unexpected 'else'".to_string(),
+            )),
+            Expr::Try(token) => {
+                let mut s = token.position().clone();
+                let body = self.expression(cursor, scope)?;
+                if let Some(Expr::Else(_)) = cursor.peek() {
+                    cursor.next()?;
+                    let fallback = self.expression(cursor, scope)?;
+                    let ty = match self.subst.join(&body.get_type(), &fallback.get_type()) {
+                        Some(ty) => ty,
+                        None => {
+                            return Err(Error::new(
+                                ErrorType::TypeError,
+                                fallback.position().clone(),
+                                format!(
+                                    "Fallback of a try must have the same type as its body, expected {}, found {}",
+                                    self.subst.apply(&body.get_type()),
+                                    self.subst.apply(&fallback.get_type())
+                                ),
+                            ))
+                        }
+                    };
+                    s.merge(fallback.position());
+                    Ok(Node::Try(Box::new(body), Some(Box::new(fallback)), ty, s))
+                } else {
+                    let ty = Type::Named(
+                        Cow::Borrowed("Result"),
+                        Cow::Owned(vec![body.get_type(), Type::Str]),
+                    );
+                    s.merge(body.position());
+                    Ok(Node::Try(Box::new(body), None, ty, s))
+                }
+            }
+            Expr::While(condition, body, pos) => {
+                let condition = self.single(condition, pos, scope, "as a condition")?;
+                if !self.subst.is_assignable(&Type::Bool, &condition.get_type()) {
+                    return Err(Error::new(
+                        ErrorType::TypeError,
+                        condition.position().clone(),
+                        "Expected bool".to_string(),
+                    ));
+                }
+                let (body, _) = self.statements(body, pos, scope)?;
+                Ok(Node::While(
+                    Box::new(condition),
+                    Box::new(Node::Statements(body, Type::None, pos.clone())),
+                    pos.clone(),
+                ))
+            }
+            Expr::For(var, range, body, pos) => {
+                let mut cursor = Cursor::new(range, pos);
+                let mut bounds = Vec::with_capacity(2);
+                for _ in 0..2 {
+                    let bound = self.expression(&mut cursor, scope)?;
+                    if !self.subst.is_assignable(&Type::Number, &bound.get_type()) {
+                        return Err(Error::new(
+                            ErrorType::TypeError,
+                            bound.position().clone(),
+                            format!(
+                                "Expected type Int, but got {}",
+                                self.subst.apply(&bound.get_type())
+                            ),
+                        ));
+                    }
+                    bounds.push(bound);
+                }
+                if let Some(expr) = cursor.peek() {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        expr.position().clone(),
+                        "Expected a start and an end for the range".to_string(),
+                    ));
+                }
+                let mut new = scope.get_new();
+                new.define((var.clone(), Type::Number));
+                let var = new.resolve(var);
+                let (body, _) = self.statements(body, pos, &mut new)?;
+                scope.add(new);
+                let end = bounds.pop().unwrap();
+                let start = bounds.pop().unwrap();
+                Ok(Node::For(
+                    var,
+                    Box::new(start),
+                    Box::new(end),
+                    Box::new(Node::Statements(body, Type::None, pos.clone())),
+                    pos.clone(),
+                ))
+            }
+            Expr::If(token) => {
+                let mut s = token.position().clone();
+                let condition = self.expression(cursor, scope)?;
+                if !self.subst.is_assignable(&Type::Bool, &condition.get_type()) {
+                    return Err(Error::new(
+                        ErrorType::TypeError,
+                        condition.position().clone(),
+                        "Expected bool".to_string(),
+                    ));
+                }
+                let then = self.expression(cursor, scope)?;
+                let else_ = self.expression(cursor, scope)?;
+                let ty = match self.subst.join(&then.get_type(), &else_.get_type()) {
+                    Some(ty) => ty,
+                    None => {
+                        return Err(Error::new(
+                        ErrorType::TypeError,
+                        else_.position().clone(),
+                        format!(
+                            "Branches of an if statement must have same types, expected {}, found {}",
+                            self.subst.apply(&then.get_type()),
+                            self.subst.apply(&else_.get_type())
+                        ),
+                    ))
+                    }
+                };
+                s.merge(else_.position());
+                Ok(Node::If(
+                    Box::new(condition),
+                    Box::new(then),
+                    Box::new(else_),
+                    ty,
+                    s,
+                ))
+            }
+        }
+    }
+
+    fn arguments(
+        &mut self,
+        token: &Token,
+        params: Vec<Type>,
+        cursor: &mut Cursor,
+        scope: &mut Scope,
+    ) -> Result<Vec<Node>, Error> {
+        let mut args = Vec::new();
+        let arity = params.len();
+        for ty in params {
+            if cursor.is_done() {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    token.position().clone(),
+                    format!(
+                        "{} takes {} arguments, but only {} were given",
+                        token,
+                        arity,
+                        args.len()
+                    ),
+                ));
+            }
+            let expr = self.expression(cursor, scope)?;
+            if !self.subst.is_assignable(&ty, &expr.get_type()) {
+                return Err(Error::new(
+                    ErrorType::TypeError,
+                    expr.position().clone(),
+                    format!(
+                        "Expected type {}, but got {}",
+                        self.subst.apply(&ty),
+                        self.subst.apply(&expr.get_type())
+                    ),
+                ));
+            }
+            args.push(expr);
+        }
+        Ok(args)
+    }
+
+    fn single(
+        &mut self,
+        exprs: &[Expr],
+        end: &Position,
+        scope: &mut Scope,
+        what: &str,
+    ) -> CheckResult {
+        let mut cursor = Cursor::new(exprs, end);
+        let value = self.expression(&mut cursor, scope)?;
+        match cursor.peek() {
+            Some(expr) => Err(Error::new(
+                ErrorType::SyntaxError,
+                expr.position().clone(),
+                format!("Expected a single expression {}", what),
+            )),
+            None => Ok(value),
+        }
+    }
+
+    fn matching(
+        &mut self,
+        target: &[Expr],
+        arms: &[(Pattern, Vec<Expr>)],
+        pos: &Position,
+        scope: &mut Scope,
+    ) -> CheckResult {
+        let target = self.single(target, pos, scope, "to match on")?;
+        let ty = self.subst.apply(&target.get_type());
+        let name = match (&ty, arms.first()) {
+            (Type::Named(name, _), _) => name.to_string(),
+            (Type::Unknown(_), Some((Pattern::Variant(variant, _), _))) => {
+                match scope.find_variant(variant) {
+                    Some((name, _)) => name.to_string(),
+                    None => String::new(),
+                }
+            }
+            _ => String::new(),
+        };
+        let (params, variants) = match scope.find_type(&name) {
+            Some(TypeDef::Enum(params, variants)) => (params.clone(), variants.clone()),
+            _ => {
+                return Err(Error::new(
+                    ErrorType::TypeError,
+                    target.position().clone(),
+                    format!("Expected an enum, but got {}", ty),
+                ))
+            }
+        };
+        let args = params
+            .iter()
+            .map(|_| self.subst.fresh())
+            .collect::<Vec<_>>();
+        self.subst.unify(
+            &ty,
+            &Type::Named(Cow::Owned(name.clone()), args.clone().into()),
+        );
+        let vars = params.into_iter().zip(args).collect::<Vec<_>>();
+        let mut covered = vec![];
+        let mut wildcard = false;
+        let mut ret: Option<Type> = None;
+        let mut checked = vec![];
+        for (pattern, body) in arms {
+            if wildcard {
+                return Err(Error::new(
+                    ErrorType::TypeError,
+                    pattern.position().clone(),
+                    "Unreachable pattern, a previous arm matches everything".to_string(),
+                ));
+            }
+            let mut inner = scope.get_new();
+            let mut resolved = pattern.clone();
+            match pattern {
+                Pattern::Wildcard(_) => wildcard = true,
+                Pattern::Variant(variant, binders) => {
+                    let payload = match variants.iter().find(|(v, _)| v == variant) {
+                        Some((_, payload)) => payload,
+                        None => {
+                            return Err(Error::new(
+                                ErrorType::TypeError,
+                                variant.position().clone(),
+                                format!("{} is not a variant of {}", variant, name),
+                            ))
+                        }
+                    };
+                    if covered.contains(&variant) {
+                        return Err(Error::new(
+                            ErrorType::TypeError,
+                            variant.position().clone(),
+                            format!("Unreachable pattern, {} is already matched", variant),
+                        ));
+                    }
+                    if binders.len() != payload.len() {
+                        return Err(Error::new(
+                            ErrorType::TypeError,
+                            variant.position().clone(),
+                            format!(
+                                "{} holds {} values, but the pattern binds {}",
+                                variant,
+                                payload.len(),
+                                binders.len()
+                            ),
+                        ));
+                    }
+                    covered.push(variant);
+                    let mut runtime = Vec::with_capacity(binders.len());
+                    for (binder, t) in binders.iter().zip(payload) {
+                        inner.define((binder.clone(), self.subst.apply(&t.replace_vars(&vars))));
+                        runtime.push(inner.resolve(binder));
+                    }
+                    resolved = Pattern::Variant(variant.clone(), runtime);
+                }
+            }
+            let body = self.single(body, pattern.position(), &mut inner, "after '~>'")?;
+            scope.add(inner);
+            ret = match ret {
+                None => Some(body.get_type()),
+                Some(ret) => match self.subst.join(&ret, &body.get_type()) {
+                    Some(t) => Some(t),
+                    None => {
+                        return Err(Error::new(
+                            ErrorType::TypeError,
+                            body.position().clone(),
+                            format!(
+                                "Arms of a match must have same types, expected {}, found {}",
+                                self.subst.apply(&ret),
+                                self.subst.apply(&body.get_type())
+                            ),
+                        ))
+                    }
+                },
+            };
+            checked.push((resolved, body));
+        }
+        if !wildcard && covered.len() < variants.len() {
+            return Err(Error::new(
+                ErrorType::TypeError,
+                pos.clone(),
+                format!(
+                    "Non-exhaustive match, missing {}",
+                    variants
+                        .iter()
+                        .filter(|(v, _)| !covered.contains(&v))
+                        .map(|(v, _)| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ));
+        }
+        let ret = ret.map_or(Type::None, |t| self.subst.apply(&t));
+        Ok(Node::Match(Box::new(target), checked, ret, pos.clone()))
+    }
+
+    fn field_values(
+        &mut self,
+        record: &Token,
+        fields: &[(Type, Token)],
+        values: &[(Token, Vec<Expr>)],
+        scope: &mut Scope,
+    ) -> Result<Vec<(Token, Node)>, Error> {
+        let mut checked: Vec<(Token, Node)> = vec![];
+        for (name, exprs) in values {
+            let ty = match fields.iter().find(|(_, f)| f == name) {
+                Some((t, _)) => t,
+                None => {
+                    return Err(Error::new(
+                        ErrorType::TypeError,
+                        name.position().clone(),
+                        format!("{} has no field {}", record, name),
+                    ))
+                }
+            };
+            if checked.iter().any(|(f, _)| f == name) {
+                return Err(Error::new(
+                    ErrorType::TypeError,
+                    name.position().clone(),
+                    format!("Field {} is given twice", name),
+                ));
+            }
+            let value = self.single(
+                exprs,
+                name.position(),
+                scope,
+                &format!("for field {}", name),
+            )?;
+            if !self.subst.is_assignable(ty, &value.get_type()) {
+                return Err(Error::new(
+                    ErrorType::TypeError,
+                    value.position().clone(),
+                    format!(
+                        "Field {} of {} expects {}, but got {}",
+                        name,
+                        record,
+                        ty,
+                        self.subst.apply(&value.get_type())
+                    ),
+                ));
+            }
+            checked.push((name.clone(), value));
+        }
+        Ok(checked)
+    }
+
+    // The record type of `target`. When it isn't known yet, it is the record
+    // with a field called `field`, as long as only one record has one.
+    fn record_type(
+        &mut self,
+        target: &Node,
+        field: &str,
+        scope: &Scope,
+    ) -> Result<(Token, Vec<(Type, Token)>), Error> {
+        let ty = self.subst.apply(&target.get_type());
+        if let Type::Unknown(_) = ty {
+            match scope.records_with_field(field)[..] {
+                [(name, fields)] => {
+                    let record = Type::Named(Cow::Owned(name.to_string()), Cow::Borrowed(&[]));
+                    self.subst.unify(&ty, &record);
+                    return Ok((name.clone(), fields.to_vec()));
+                }
+                ref records @ [_, _, ..] => {
+                    return Err(Error::new(
+                        ErrorType::TypeError,
+                        target.position().clone(),
+                        format!(
+                            "Ambiguous field {}, which could be in {}",
+                            field,
+                            records
+                                .iter()
+                                .map(|(name, _)| name.to_string())
+                                .collect::<Vec<_>>()
+                                .join(" or ")
+                        ),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        if let Type::Named(ref name, _) = ty {
+            if let Some((token, TypeDef::Record(fields))) = scope.find_type_token(name) {
+                return Ok((token.clone(), fields.clone()));
+            }
+        }
+        Err(Error::new(
+            ErrorType::TypeError,
+            target.position().clone(),
+            format!("Expected a record, but got {}", ty),
+        ))
+    }
+
+    fn find(&mut self, scope: &Scope, token: &Token) -> Result<Type, Error> {
+        if let Some(capability) = scope.builtin(token).and_then(|f| f.capability()) {
+            if !self.capabilities.contains(&capability) {
+                return Err(Error::new(
+                    ErrorType::CapabilityError,
+                    token.position().clone(),
+                    format!(
+                        "{} needs the {} capability, which has not been granted",
+                        token, capability
+                    ),
+                ));
+            }
+        }
+        if let Some(owner) = scope.private_owner(token) {
+            return Err(Error::new(
+                ErrorType::UndefinedFunction,
+                token.position().clone(),
+                format!("{} exists in {} but is private", token, owner),
+            ));
+        }
+        match scope.find(token) {
+            Some((t, true)) => Ok(self.subst.instantiate(&t)),
+            Some((t, false)) => Ok(t),
+            None => Err(Error::new(
+                ErrorType::UndefinedFunction,
+                token.position().clone(),
+                format!("Undefined Function : {}", token),
+            )),
+        }
+    }
+
+    fn function(
+        &mut self,
+        params: &[(TypeExpr, Token)],
+        ret: &Option<TypeExpr>,
+        body: &[Expr],
+        pos: &Position,
+        scope: &mut Scope,
+        name: Option<(&Token, Option<String>)>,
+    ) -> CheckResult {
+        let (types, ret) = self.signature(params, ret, scope)?;
+        let params = types
+            .into_iter()
+            .zip(params.iter().map(|(_, p)| p.clone()))
+            .collect::<Vec<_>>();
+        let mut inner = scope.get_new();
+        if let Some((name, runtime)) = name {
+            inner.define_alias(
+                (
+                    name.clone(),
+                    Type::Function(
+                        params.iter().map(|(t, _)| t.clone()).collect(),
+                        Box::new(ret.clone()),
+                    ),
+                ),
+                runtime,
+            );
+        }
+        let mut runtime = Vec::with_capacity(params.len());
+        for (t, p) in &params {
+            inner.define((p.clone(), t.clone()));
+            runtime.push(inner.resolve(p));
+        }
+        let (stmts, ty) = self.statements(body, pos, &mut inner)?;
+        if !self.subst.is_assignable(&ret, &ty) {
+            return Err(Error::new(
+                ErrorType::TypeError,
+                stmts.last().map_or(pos, |n| n.position()).clone(),
+                format!(
+                    "Return type mismatch, expected {}, found {}",
+                    self.subst.apply(&ret),
+                    self.subst.apply(&ty)
+                ),
+            ));
+        }
+        scope.add(inner);
+        self.generalize(scope, &params, &ret);
+        Ok(Node::Function(
+            UserDefinedFunction::new(
+                params
+                    .into_iter()
+                    .zip(runtime)
+                    .map(|((t, _), p)| (self.subst.apply(&t), p))
+                    .collect(),
+                self.subst.apply(&ret),
+                stmts,
+            ),
+            pos.clone(),
+        ))
+    }
+
+    fn load(&mut self, path: &Path, pos: &Position) -> Result<Rc<Module>, Error> {
+        let (contents, mut scope) = self.loader.start(path, pos)?;
+        let tokens = lexer::lex(&contents, path.display().to_string());
+        let mut checker = Checker {
+            subst: Substitution::new(),
+            loader: &mut *self.loader,
+            capabilities: self.capabilities,
+            depth: 0,
+        };
+        let statements = match tokens.and_then(|tokens| parser::parse(&tokens)) {
+            Ok(Expr::Block(exprs, end)) => checker.top_level(&exprs, &end, &mut scope),
+            Ok(_) => unreachable!(),
+            Err(err) => Err(err),
+        };
+        let exports = scope
+            .exports()
+            .into_iter()
+            .map(|export| Export {
+                ty: checker.subst.apply(&export.ty),
+                ..export
+            })
+            .collect();
+        match statements {
+            Ok(statements) => Ok(self.loader.finish(path, exports, &scope, statements)),
+            Err(err) => {
+                self.loader.abort(path);
+                Err(err)
+            }
+        }
+    }
+
+    fn generalize(&mut self, scope: &Scope, params: &[(Type, Token)], ret: &Type) {
+        let mut env = vec![];
+        for t in scope.monomorphic() {
+            self.subst.apply(t).unknowns(&mut env);
+        }
+        let mut names = vec![];
+        let mut unknowns = vec![];
+        for t in params.iter().map(|(t, _)| t).chain([ret]) {
+            let t = self.subst.apply(t);
+            t.vars(&mut names);
+            t.unknowns(&mut unknowns);
+        }
+        let mut candidates = ('A'..='Z').map(|c| c.to_string());
+        for n in unknowns {
+            if env.contains(&n) {
+                continue;
+            }
+            let name = candidates
+                .by_ref()
+                .find(|c| !names.contains(c))
+                .unwrap_or_else(|| format!("T{}", n));
+            self.subst
+                .unify(&Type::Unknown(n), &Type::Var(Cow::Owned(name)));
+        }
+    }
+}
+
+pub fn check_in(
+    exprs: &[Expr],
+    end: &Position,
+    scope: &mut Scope,
+    subst: &mut Substitution,
+    loader: &mut Loader,
+    capabilities: &[Capability],
+) -> Result<Vec<Node>, Error> {
+    let mut checker = Checker {
+        subst: std::mem::take(subst),
+        loader,
+        capabilities,
+        depth: 0,
+    };
+    let result = checker.top_level(exprs, end, scope);
+    *subst = checker.subst;
+    result
+}
+
+// For editors, which want to know about the parts of a program that check
+// even when another part doesn't.
+pub fn check_partial(
+    exprs: &[Expr],
+    end: &Position,
+    scope: &mut Scope,
+    subst: &mut Substitution,
+    loader: &mut Loader,
+    capabilities: &[Capability],
+) -> (Node, Option<Error>) {
+    let mut checker = Checker {
+        subst: std::mem::take(subst),
+        loader,
+        capabilities,
+        depth: 0,
+    };
+    let (statements, error) = checker.prefix(exprs, end, scope);
+    *subst = checker.subst;
+    (Node::Statements(statements, Type::None, end.clone()), error)
+}
+
+pub fn check(
+    ast: &Expr,
+    scope: &mut Scope,
+    subst: &mut Substitution,
+    loader: &mut Loader,
+    capabilities: &[Capability],
+) -> CheckResult {
+    let mut checker = Checker {
+        subst: std::mem::take(subst),
+        loader,
+        capabilities,
+        depth: 0,
+    };
+    let result = match ast {
+        Expr::Block(exprs, pos) => checker
+            .block(exprs, pos, scope)
+            .map(|(statements, ty)| Node::Statements(statements, ty, pos.clone())),
+        _ => checker.expression(
+            &mut Cursor::new(std::slice::from_ref(ast), ast.position()),
+            scope,
+        ),
+    };
+    *subst = checker.subst;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checks a program without the prelude, returning its statements and the
+    // substitution their types are solved in.
+    fn checked(source: &str) -> Result<(Vec<Node>, Substitution), Error> {
+        let ast = parser::parse(&lexer::lex(source, "test.tru".to_string())?)?;
+        let mut scope = Scope::new();
+        let mut subst = Substitution::new();
+        let mut loader = Loader::new(scope.clone());
+        match check(&ast, &mut scope, &mut subst, &mut loader, &Capability::ALL)? {
+            Node::Statements(statements, ..) => Ok((statements, subst)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn types(source: &str) -> Vec<String> {
+        let (statements, subst) = checked(source).unwrap();
+        statements
+            .iter()
+            .map(|s| match s {
+                Node::Let(_, _, ty, _) => subst.apply(ty).to_string(),
+                Node::Define(_, value) => subst.apply(&value.get_type()).to_string(),
+                s => subst.apply(&s.get_type()).to_string(),
+            })
+            .collect()
+    }
+
+    fn error(source: &str) -> Error {
+        checked(source).unwrap_err()
+    }
+
+    #[test]
+    fn calls_take_as_many_arguments_as_they_need() {
+        let (statements, _) = checked("+ 1 * 2 3").unwrap();
+        assert_eq!(statements.len(), 1);
+        assert_eq!(
+            statements[0].to_string(),
+            "Call[+][Number[1], Call[*][Number[2], Number[3]]]"
+        );
+    }
+
+    #[test]
+    fn let_bindings_are_inferred() {
+        assert_eq!(
+            types("let a : + 1 2\nlet b : \"b\"\nlet c : == a 3"),
+            ["Int", "Str", "Bool"]
+        );
+    }
+
+    #[test]
+    fn generic_functions_are_instantiated_at_each_use() {
+        assert_eq!(
+            types("identity : [T] x ~> [T] | x |\nidentity 1\nidentity == 1 1"),
+            ["[T] ~> [T]", "Int", "Bool"]
+        );
+    }
+
+    #[test]
+    fn unannotated_parameters_are_inferred() {
+        assert_eq!(
+            types("fact : [] n | ? == n 0 1 * n fact - n 1 |"),
+            ["[Int] ~> [Int]"]
+        );
+    }
+
+    #[test]
+    fn shadowing_bindings_get_their_own_runtime_name() {
+        let (statements, _) = checked("let x : 1\nlet x : \"a\"").unwrap();
+        let names = statements
+            .iter()
+            .map(|s| match s {
+                Node::Let(t, ..) => match &**t {
+                    TokenType::Word(name) => name.clone(),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names[0], "x");
+        assert!(names[1].starts_with("x#"));
+    }
+
+    #[test]
+    fn mismatched_argument() {
+        assert_eq!(
+            error("+ 1 == 1 1").to_string(),
+            "TypeError at 1:5 to 1:7 in test.tru ~> Expected type Int, but got Bool"
+        );
+    }
+
+    #[test]
+    fn undefined_name() {
+        assert_eq!(
+            error("missing 1").to_string(),
+            "UndefinedFunction at 1:1 to 1:8 in test.tru ~> Undefined Function : missing"
+        );
+    }
+
+    #[test]
+    fn field_shared_by_two_records() {
+        let records = "record A { [Int] x }\nrecord B { [Int] x [Int] y }\n";
+        assert_eq!(
+            error(&format!("{}get : [] p | .x p |", records)).to_string(),
+            "TypeError at 3:17 to 3:18 in test.tru ~> Ambiguous field x, which could be in A or B"
+        );
+        assert_eq!(
+            types(&format!("{}get : [] p | .y p |", records))[2],
+            "[B] ~> [Int]"
+        );
+    }
+
+    #[test]
+    fn assigning_to_an_immutable_binding() {
+        assert_eq!(
+            error("let a : 1\na <- 2").to_string(),
+            "TypeError at 2:1 to 2:2 in test.tru ~> Cannot assign to a, it is not mutable"
+        );
+    }
+
+    #[test]
+    fn missing_capability() {
+        let ast = parser::parse(&lexer::lex(". 1", "test.tru".to_string()).unwrap()).unwrap();
+        let mut scope = Scope::new();
+        let mut loader = Loader::new(scope.clone());
+        let err = check(&ast, &mut scope, &mut Substitution::new(), &mut loader, &[]).unwrap_err();
+        assert_eq!(*err.error_type(), ErrorType::CapabilityError);
+    }
+}
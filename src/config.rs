@@ -0,0 +1,188 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capability {
+    Filesystem,
+    Stdin,
+    Print,
+    Environment,
+    Clock,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [
+        Capability::Filesystem,
+        Capability::Stdin,
+        Capability::Print,
+        Capability::Environment,
+        Capability::Clock,
+    ];
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Capability::Filesystem => write!(f, "filesystem"),
+            Capability::Stdin => write!(f, "stdin"),
+            Capability::Print => write!(f, "print"),
+            Capability::Environment => write!(f, "environment"),
+            Capability::Clock => write!(f, "clock"),
+        }
+    }
+}
+
+// Steps count every node evaluated, depth counts nested calls to user
+// functions, and memory counts the bytes taken by strings, records and
+// variants built while running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub steps: Option<u64>,
+    pub depth: Option<usize>,
+    pub memory: Option<usize>,
+}
+
+impl Limits {
+    pub fn unlimited() -> Limits {
+        Limits {
+            steps: None,
+            depth: None,
+            memory: None,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            depth: Some(1000),
+            ..Limits::unlimited()
+        }
+    }
+}
+
+// Clones share the same flag, so a host can keep one and cancel a script
+// running on another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn with_timeout(timeout: Duration) -> CancellationToken {
+        CancellationToken {
+            deadline: Some(Instant::now() + timeout),
+            ..CancellationToken::default()
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+// Where `.` and `print` write to.
+#[derive(Clone)]
+pub enum Output {
+    Stdout,
+    Writer(Arc<Mutex<dyn Write + Send>>),
+    Callback(Arc<dyn Fn(&str) + Send + Sync>),
+}
+
+impl Output {
+    pub fn writer(writer: impl Write + Send + 'static) -> Output {
+        Output::Writer(Arc::new(Mutex::new(writer)))
+    }
+
+    pub fn callback(callback: impl Fn(&str) + Send + Sync + 'static) -> Output {
+        Output::Callback(Arc::new(callback))
+    }
+
+    pub fn discard() -> Output {
+        Output::writer(io::sink())
+    }
+
+    pub fn write(&self, text: &str) -> io::Result<()> {
+        match self {
+            Output::Stdout => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(text.as_bytes())?;
+                stdout.flush()
+            }
+            Output::Writer(writer) => {
+                let mut writer = writer.lock().unwrap_or_else(|err| err.into_inner());
+                writer.write_all(text.as_bytes())?;
+                writer.flush()
+            }
+            Output::Callback(callback) => {
+                callback(text);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Output::Stdout => write!(f, "Stdout"),
+            Output::Writer(_) => write!(f, "Writer"),
+            Output::Callback(_) => write!(f, "Callback"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub prelude: bool,
+    pub args: Vec<String>,
+    pub capabilities: Vec<Capability>,
+    pub limits: Limits,
+    pub cancellation: CancellationToken,
+    pub output: Output,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            prelude: true,
+            args: Vec::new(),
+            capabilities: Capability::ALL.to_vec(),
+            limits: Limits::default(),
+            cancellation: CancellationToken::new(),
+            output: Output::Stdout,
+        }
+    }
+
+    pub fn sandboxed() -> Config {
+        Config {
+            capabilities: Vec::new(),
+            ..Config::new()
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
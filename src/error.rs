@@ -1,10 +1,11 @@
-use std::{error::Error as stdError, fmt, rc::Rc};
+use std::{error::Error as stdError, fmt, sync::Arc};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Error {
     error: ErrorType,
     position: Position,
     details: String,
+    trace: Vec<(String, Position)>,
 }
 
 impl Error {
@@ -13,23 +14,42 @@ impl Error {
             error,
             position,
             details,
+            trace: Vec::new(),
         }
     }
+
+    pub fn error_type(&self) -> &ErrorType {
+        &self.error
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn details(&self) -> &str {
+        &self.details
+    }
+
+    pub fn trace(&self) -> &[(String, Position)] {
+        &self.trace
+    }
+
+    pub fn push_frame(&mut self, name: String, position: Position) {
+        self.trace.push((name, position));
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{} at {}:{} to {}:{} in {} ~> {}",
-            self.error,
-            self.position.line,
-            self.position.column,
-            self.position.line_end,
-            self.position.column_end,
-            self.position.file,
-            self.details
-        )
+        write!(f, "{} at {} ~> {}", self.error, self.position, self.details)?;
+        for frames in self.trace.chunk_by(|a, b| a == b) {
+            let (name, pos) = &frames[0];
+            write!(f, "\n    in {} called at {}", name, pos)?;
+            if frames.len() > 1 {
+                write!(f, " ({} times)", frames.len())?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -41,6 +61,14 @@ pub enum ErrorType {
     UndefinedFunction,
     TypeError,
     DivisionByZero,
+    UserError,
+    ImportError,
+    DomainError,
+    IoError,
+    CapabilityError,
+    LimitError,
+    Cancelled,
+    AssertionError,
 }
 
 impl fmt::Display for ErrorType {
@@ -55,7 +83,7 @@ pub struct Position {
     column: usize,
     line_end: usize,
     column_end: usize,
-    file: Rc<String>,
+    file: Arc<String>,
 }
 
 impl Position {
@@ -64,7 +92,7 @@ impl Position {
         line_end: usize,
         column: usize,
         column_end: usize,
-        file: Rc<String>,
+        file: Arc<String>,
     ) -> Position {
         Position {
             line,
@@ -75,8 +103,38 @@ impl Position {
         }
     }
 
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn line_end(&self) -> usize {
+        self.line_end
+    }
+
+    pub fn column_end(&self) -> usize {
+        self.column_end
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
     pub fn merge(&mut self, other: &Self) {
         self.line_end = other.line_end;
         self.column_end = other.column_end;
     }
 }
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} to {}:{} in {}",
+            self.line, self.column, self.line_end, self.column_end, self.file
+        )
+    }
+}
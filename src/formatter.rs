@@ -0,0 +1,222 @@
+use crate::{
+    ast::Expr,
+    error::{Error, Position},
+    lexer, parser,
+    token::{Token, TokenType},
+};
+
+// Where one statement ends is only known once names are resolved, so the
+// formatter keeps the line breaks of the source, with at most two blank lines
+// in a row, and only normalizes the spacing and indentation of each line.
+pub fn format(source: &str, file: &str) -> Result<String, Error> {
+    let closing = closing_pipes(&parser::parse(&lexer::lex(source, file.to_string())?)?);
+    let lines = source.split('\n').collect::<Vec<_>>();
+    let tokens = lexer::lex_with_comments(source, file.to_string());
+    let mut output = String::new();
+    // The line each open bracket, brace or pipe was opened on.
+    let mut open = Vec::new();
+    let mut previous: Option<&Token> = None;
+    for (i, token) in tokens.iter().enumerate() {
+        if let TokenType::Eof = **token {
+            break;
+        }
+        match previous {
+            Some(previous) if token.position().line() == previous.position().line_end() => {
+                if spaced(previous, token) {
+                    output.push(' ');
+                }
+            }
+            _ => {
+                if let Some(previous) = previous {
+                    let gap = token.position().line() - previous.position().line_end();
+                    output.push_str(&"\n".repeat(gap.min(3)));
+                }
+                output.push_str(&"  ".repeat(indent(&open, &closing, &tokens[i..])));
+            }
+        }
+        output.push_str(&text(&lines, token));
+        match **token {
+            TokenType::LBracket | TokenType::LBrace => open.push(token.position().line()),
+            TokenType::RBracket | TokenType::RBrace => {
+                open.pop();
+            }
+            TokenType::Pipe if closes(&closing, token) => {
+                open.pop();
+            }
+            TokenType::Pipe => open.push(token.position().line()),
+            _ => {}
+        }
+        previous = Some(token);
+    }
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+fn spaced(previous: &Token, token: &Token) -> bool {
+    !matches!(**previous, TokenType::LBracket)
+        && !matches!(**token, TokenType::RBracket | TokenType::Bang)
+}
+
+// A line is indented once for every earlier line that left something open,
+// not counting what the line itself starts by closing.
+fn indent(open: &[usize], closing: &[(usize, usize)], tokens: &[Token]) -> usize {
+    let line = tokens[0].position().line();
+    let mut depth = open.len();
+    for token in tokens.iter().take_while(|t| t.position().line() == line) {
+        match **token {
+            TokenType::RBracket | TokenType::RBrace => depth = depth.saturating_sub(1),
+            TokenType::Pipe if closes(closing, token) => depth = depth.saturating_sub(1),
+            _ => break,
+        }
+    }
+    let mut lines = open[..depth].to_vec();
+    lines.dedup();
+    lines.len()
+}
+
+// Whether a `|` opens or closes a body depends on what comes before it, so
+// the pipes that close one are taken from the parsed program: they are where
+// functions and blocks end.
+fn closing_pipes(expr: &Expr) -> Vec<(usize, usize)> {
+    let mut closing = Vec::new();
+    collect_closing(std::slice::from_ref(expr), &mut closing);
+    closing
+}
+
+fn collect_closing(exprs: &[Expr], closing: &mut Vec<(usize, usize)>) {
+    for expr in exprs {
+        match expr {
+            Expr::Block(body, pos) | Expr::Function(.., body, pos) => {
+                closing.push((pos.line_end(), pos.column_end()));
+                collect_closing(body, closing);
+            }
+            Expr::Construct(_, fields, _) | Expr::Fields(fields, _) => {
+                for (_, value) in fields {
+                    collect_closing(value, closing);
+                }
+            }
+            Expr::Match(target, arms, _) => {
+                collect_closing(target, closing);
+                for (_, body) in arms {
+                    collect_closing(body, closing);
+                }
+            }
+            Expr::While(first, body, _) | Expr::For(_, first, body, _) => {
+                collect_closing(first, closing);
+                collect_closing(body, closing);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn closes(closing: &[(usize, usize)], token: &Token) -> bool {
+    let pos = token.position();
+    closing.contains(&(pos.line_end(), pos.column_end()))
+}
+
+// Literals are copied from the source, so they keep their escapes and the
+// way their numbers are written.
+fn text(lines: &[&str], token: &Token) -> String {
+    match **token {
+        TokenType::Number(_) | TokenType::Str(_) => source(lines, token.position()),
+        _ => token.to_string(),
+    }
+}
+
+fn source(lines: &[&str], pos: &Position) -> String {
+    let (start, end) = (pos.line() - 1, pos.line_end() - 1);
+    let mut text = String::new();
+    for (n, line) in lines[start..=end].iter().enumerate() {
+        if n > 0 {
+            text.push('\n');
+        }
+        let from = if n == 0 { pos.column() - 1 } else { 0 };
+        if start + n == end {
+            text.extend(line.chars().take(pos.column_end() - 1).skip(from));
+        } else {
+            text.extend(line.chars().skip(from));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(source: &str) -> String {
+        format(source, "test.tru").unwrap()
+    }
+
+    #[test]
+    fn nested_function_bodies() {
+        let source = "f : [Int] a ~> [Int] |
+b : [Int] c ~> [Int] |
+* c 2
+|
+b a
+|
+. f 3
+";
+        assert_eq!(
+            formatted(source),
+            "f : [Int] a ~> [Int] |
+  b : [Int] c ~> [Int] |
+    * c 2
+  |
+  b a
+|
+. f 3
+"
+        );
+    }
+
+    #[test]
+    fn nested_function_on_one_line() {
+        let source = "f : [Int] a ~> [Int] |\nb : [Int] c ~> [Int] | * c 2 |\nb a\n|\n";
+        assert_eq!(
+            formatted(source),
+            "f : [Int] a ~> [Int] |\n  b : [Int] c ~> [Int] | * c 2 |\n  b a\n|\n"
+        );
+    }
+
+    #[test]
+    fn function_inside_a_loop_and_a_block() {
+        let source = "let mut n : 0
+while < n 3 {
+step : [Int] x ~> [Int] |
++ x 1
+|
+n <- step n
+}
+. |
+let m : 2
+* m n
+|
+";
+        assert_eq!(
+            formatted(source),
+            "let mut n : 0
+while < n 3 {
+  step : [Int] x ~> [Int] |
+    + x 1
+  |
+  n <- step n
+}
+. |
+  let m : 2
+  * m n
+|
+"
+        );
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let source = "f : [Int] a ~> [Int] |\n  b : [Int] c ~> [Int] |\n    * c 2\n  |\n  b a\n|\n";
+        assert_eq!(formatted(source), source);
+    }
+}
@@ -1,17 +1,23 @@
-use std::fmt;
+use std::{borrow::Cow, fmt, rc::Rc};
 
-use crate::{node::Node, token::Token};
+use crate::{config::Capability, node::Node, token::Token};
 
+// The parameters and body are shared, so the copies made each time a function
+// is looked up stay cheap.
 #[derive(Debug, Clone, PartialEq)]
 pub struct UserDefinedFunction {
-    params: Vec<(Type, Token)>,
+    params: Rc<[(Type, Token)]>,
     ret: Type,
-    body: Vec<Node>,
+    body: Rc<[Node]>,
 }
 
 impl UserDefinedFunction {
     pub fn new(params: Vec<(Type, Token)>, ret: Type, body: Vec<Node>) -> Self {
-        Self { params, ret, body }
+        Self {
+            params: params.into(),
+            ret,
+            body: body.into(),
+        }
     }
 
     pub fn params(&self) -> &[(Type, Token)] {
@@ -22,7 +28,7 @@ impl UserDefinedFunction {
         &self.ret
     }
 
-    pub fn body(&self) -> &Vec<Node> {
+    pub fn body(&self) -> &[Node] {
         &self.body
     }
 }
@@ -31,12 +37,11 @@ impl fmt::Display for UserDefinedFunction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{} ~> [{}]",
+            "{}~> [{}]",
             self.params
                 .iter()
-                .map(|a| format!("[{}]", a.0))
-                .collect::<Vec<String>>()
-                .join(" "),
+                .map(|a| format!("[{}] ", a.0))
+                .collect::<String>(),
             self.ret,
         )
     }
@@ -47,11 +52,26 @@ pub struct BuiltInFunction {
     name: &'static str,
     params: &'static [Type],
     ret: Type,
+    capability: Option<Capability>,
 }
 
 impl BuiltInFunction {
     pub const fn new(name: &'static str, params: &'static [Type], ret: Type) -> Self {
-        Self { name, params, ret }
+        Self {
+            name,
+            params,
+            ret,
+            capability: None,
+        }
+    }
+
+    pub const fn requires(mut self, capability: Capability) -> Self {
+        self.capability = Some(capability);
+        self
+    }
+
+    pub const fn capability(&self) -> Option<Capability> {
+        self.capability
     }
 
     pub const fn params(&self) -> &[Type] {
@@ -67,33 +87,83 @@ impl BuiltInFunction {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Number,
     None,
     Any,
+    Str,
     Bool,
+    Var(Cow<'static, str>),
+    Unknown(usize),
+    Named(Cow<'static, str>, Cow<'static, [Type]>),
     Function(Vec<Type>, Box<Type>),
 }
 
-impl PartialEq for Type {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (_, Type::Any)
-            | (Type::Any, _)
-            | (Type::Number, Type::Number)
-            | (Type::Bool, Type::Bool)
-            | (Type::None, Type::None) => true,
-            (Type::Function(a, b), Type::Function(c, d)) => {
-                if a.len() != c.len() {
-                    return false;
-                }
-                for (a, c) in a.iter().zip(c.iter()) {
-                    if !a.eq(c) {
-                        return false;
-                    }
-                }
-                b.eq(d)
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeDef {
+    Record(Vec<(Type, Token)>),
+    Enum(Vec<String>, Vec<(Token, Vec<Type>)>),
+}
+
+impl TypeDef {
+    pub fn params(&self) -> &[String] {
+        match self {
+            TypeDef::Record(_) => &[],
+            TypeDef::Enum(params, _) => params,
+        }
+    }
+}
+
+impl Type {
+    pub fn replace_vars(&self, vars: &[(String, Type)]) -> Type {
+        match self {
+            Type::Var(name) => match vars.iter().find(|(v, _)| v == name) {
+                Some((_, t)) => t.clone(),
+                None => self.clone(),
+            },
+            Type::Named(name, args) => Type::Named(
+                name.clone(),
+                args.iter().map(|a| a.replace_vars(vars)).collect(),
+            ),
+            Type::Function(params, ret) => Type::Function(
+                params.iter().map(|p| p.replace_vars(vars)).collect(),
+                Box::new(ret.replace_vars(vars)),
+            ),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn unknowns(&self, found: &mut Vec<usize>) {
+        match self {
+            Type::Unknown(n) if !found.contains(n) => found.push(*n),
+            Type::Named(_, args) => args.iter().for_each(|a| a.unknowns(found)),
+            Type::Function(params, ret) => {
+                params.iter().for_each(|p| p.unknowns(found));
+                ret.unknowns(found);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn vars(&self, found: &mut Vec<String>) {
+        match self {
+            Type::Var(name) if !found.iter().any(|f| f == name) => found.push(name.to_string()),
+            Type::Named(_, args) => args.iter().for_each(|a| a.vars(found)),
+            Type::Function(params, ret) => {
+                params.iter().for_each(|p| p.vars(found));
+                ret.vars(found);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn contains_unknown(&self, n: usize) -> bool {
+        match self {
+            Type::Unknown(m) => *m == n,
+            Type::Named(_, args) => args.iter().any(|a| a.contains_unknown(n)),
+            Type::Function(params, ret) => {
+                params.iter().any(|p| p.contains_unknown(n)) || ret.contains_unknown(n)
             }
             _ => false,
         }
@@ -106,15 +176,24 @@ impl fmt::Display for Type {
             Type::Number => write!(f, "Int"),
             Type::Bool => write!(f, "Bool"),
             Type::None => write!(f, "None"),
-            Type::Any => write!(f, "?"),
+            Type::Any => write!(f, "Any"),
+            Type::Str => write!(f, "Str"),
+            Type::Var(name) => write!(f, "{}", name),
+            Type::Unknown(n) => write!(f, "_{}", n),
+            Type::Named(name, args) => {
+                write!(f, "{}", name)?;
+                for arg in args.iter() {
+                    write!(f, " [{}]", arg)?;
+                }
+                Ok(())
+            }
             Type::Function(params, ret) => write!(
                 f,
-                "{} ~> [{}]",
+                "{}~> [{}]",
                 params
                     .iter()
-                    .map(|a| format!("[{}]", a))
-                    .collect::<Vec<String>>()
-                    .join(" "),
+                    .map(|a| format!("[{}] ", a))
+                    .collect::<String>(),
                 ret
             ),
         }
@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    analysis::{Analysis, SymbolKind},
+    error::Position,
+    token::{Token, TokenType},
+    DEFINED_WORDS,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SemanticKind {
+    Builtin,
+    Function,
+    Parameter,
+    Variable,
+    Type,
+    TypeKeyword,
+    Keyword,
+    Literal,
+    Field,
+    Punctuation,
+    Comment,
+}
+
+impl SemanticKind {
+    pub const ALL: [SemanticKind; 11] = [
+        SemanticKind::Builtin,
+        SemanticKind::Function,
+        SemanticKind::Parameter,
+        SemanticKind::Variable,
+        SemanticKind::Type,
+        SemanticKind::TypeKeyword,
+        SemanticKind::Keyword,
+        SemanticKind::Literal,
+        SemanticKind::Field,
+        SemanticKind::Punctuation,
+        SemanticKind::Comment,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SemanticKind::Builtin => "builtin",
+            SemanticKind::Function => "function",
+            SemanticKind::Parameter => "parameter",
+            SemanticKind::Variable => "variable",
+            SemanticKind::Type => "type",
+            SemanticKind::TypeKeyword => "type-keyword",
+            SemanticKind::Keyword => "keyword",
+            SemanticKind::Literal => "literal",
+            SemanticKind::Field => "field",
+            SemanticKind::Punctuation => "punctuation",
+            SemanticKind::Comment => "comment",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SemanticToken {
+    pub kind: SemanticKind,
+    pub position: Position,
+}
+
+// Words are told apart by what the checker resolved them to. When the program
+// doesn't check, only the builtins can still be recognized.
+pub fn classify(tokens: &[Token], analysis: &Analysis) -> Vec<SemanticToken> {
+    let symbols = analysis
+        .symbols
+        .iter()
+        .map(|s| ((s.position.line(), s.position.column()), s.kind))
+        .collect::<HashMap<_, _>>();
+    tokens
+        .iter()
+        .filter_map(|token| {
+            let pos = token.position();
+            let kind = match **token {
+                TokenType::Eof => return None,
+                TokenType::Number(_) | TokenType::Str(_) => SemanticKind::Literal,
+                TokenType::Comment(_) => SemanticKind::Comment,
+                TokenType::Field(_) => SemanticKind::Field,
+                TokenType::Keyword(ref k) => match k.as_str() {
+                    "Int" | "Bool" | "Str" | "Any" => SemanticKind::TypeKeyword,
+                    "true" | "false" => SemanticKind::Literal,
+                    _ => SemanticKind::Keyword,
+                },
+                TokenType::Word(ref word) => match symbols.get(&(pos.line(), pos.column())) {
+                    Some(SymbolKind::Builtin) => SemanticKind::Builtin,
+                    Some(SymbolKind::Function) => SemanticKind::Function,
+                    Some(SymbolKind::Parameter) => SemanticKind::Parameter,
+                    Some(SymbolKind::Variable | SymbolKind::Keyword) => SemanticKind::Variable,
+                    None if word.starts_with(char::is_uppercase) => SemanticKind::Type,
+                    None if DEFINED_WORDS.iter().any(|f| f.name() == word) => SemanticKind::Builtin,
+                    None => SemanticKind::Variable,
+                },
+                _ => SemanticKind::Punctuation,
+            };
+            Some(SemanticToken {
+                kind,
+                position: pos.clone(),
+            })
+        })
+        .collect()
+}
+
+pub fn html(source: &str, tokens: &[SemanticToken]) -> String {
+    let mut starts = HashMap::new();
+    let mut ends = HashSet::new();
+    for token in tokens {
+        let pos = &token.position;
+        let (start, end) = (
+            (pos.line(), pos.column()),
+            (pos.line_end(), pos.column_end()),
+        );
+        if start < end {
+            starts.insert(start, token.kind);
+            ends.insert(end);
+        }
+    }
+    let mut html = String::from("<pre class=\"trulang\">");
+    let (mut line, mut column) = (1, 1);
+    for c in source.chars() {
+        if ends.contains(&(line, column)) {
+            html.push_str("</span>");
+        }
+        if let Some(kind) = starts.get(&(line, column)) {
+            html.push_str(&format!("<span class=\"{}\">", kind.name()));
+        }
+        match c {
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            '"' => html.push_str("&quot;"),
+            _ => html.push(c),
+        }
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    if ends.contains(&(line, column)) {
+        html.push_str("</span>");
+    }
+    html.push_str("</pre>\n");
+    html
+}
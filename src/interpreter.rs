@@ -1,90 +1,505 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    rc::Rc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
-    error::{Error, ErrorType},
+    ast::Pattern,
+    config::{CancellationToken, Config, Limits, Output},
+    error::{Error, ErrorType, Position},
     node::Node,
     token::{Token, TokenType},
     value::Value,
 };
 
-pub fn interpret(ast: &Node) -> Result<(), Error> {
-    inner_interpret(ast, &mut HashMap::new()).map(|_| ())
+type BuiltIn = Box<dyn FnOnce(&[Value]) -> Result<Value, Error>>;
+
+// When less than RED_ZONE bytes of stack are left, evaluation continues on a
+// new STACK_SEGMENT allocated on the heap.
+const RED_ZONE: usize = 128 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+// The variables of a block or a call, and the frame it was created in. A
+// function runs in a frame made from the one it was defined in, so it sees
+// the names around its definition and not those of its caller. The top level
+// lives in the map given by the host.
+#[derive(Debug, Default)]
+pub struct Frame {
+    vars: RefCell<HashMap<Token, Value>>,
+    parent: Option<Rc<Frame>>,
 }
 
-fn inner_interpret(ast: &Node, vars: &mut HashMap<Token, Value>) -> Result<Value, Error> {
-    match ast {
-        Node::Number(n) => Ok(Value::from_token(n)),
-        Node::Call(func, args, _) => {
-            let args = args
-                .iter()
-                .map(|a| inner_interpret(a, vars))
-                .collect::<Vec<_>>();
-            let mut new_args = Vec::with_capacity(args.capacity());
-            for arg in args {
-                new_args.push(arg?);
-            }
-            let ret = if let Some(func) = vars.get(func).cloned() {
-                let func = if let Value::Function(f) = func {
-                    f
-                } else {
-                    unreachable!();
-                };
-                let mut new = vars.clone();
-                for ((_, p), a) in func.params().iter().zip(new_args.iter()) {
-                    new.insert(p.clone(), a.clone());
+impl Frame {
+    fn new(parent: Option<Rc<Frame>>) -> Frame {
+        Frame {
+            vars: RefCell::new(HashMap::new()),
+            parent,
+        }
+    }
+}
+
+impl PartialEq for Frame {
+    fn eq(&self, other: &Frame) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub struct Interpreter {
+    args: Vec<String>,
+    limits: Limits,
+    cancellation: CancellationToken,
+    output: Output,
+    frame: Option<Rc<Frame>>,
+    steps: u64,
+    depth: usize,
+    allocated: usize,
+}
+
+impl Interpreter {
+    pub fn new(config: &Config) -> Interpreter {
+        Interpreter {
+            args: config.args.clone(),
+            limits: config.limits,
+            cancellation: config.cancellation.clone(),
+            output: config.output.clone(),
+            frame: None,
+            steps: 0,
+            depth: 0,
+            allocated: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.steps = 0;
+        self.allocated = 0;
+    }
+
+    pub fn interpret(&mut self, ast: &Node, vars: &mut HashMap<Token, Value>) -> Result<(), Error> {
+        self.inner_interpret(ast, vars).map(|_| ())
+    }
+
+    pub fn evaluate(
+        &mut self,
+        ast: &Node,
+        vars: &mut HashMap<Token, Value>,
+    ) -> Result<Value, Error> {
+        self.inner_interpret(ast, vars)
+    }
+
+    pub fn execute(
+        &mut self,
+        statements: &[Node],
+        vars: &mut HashMap<Token, Value>,
+    ) -> Result<(), Error> {
+        hoist(statements, vars);
+        for statement in statements {
+            self.inner_interpret(statement, vars)?;
+        }
+        Ok(())
+    }
+
+    fn inner_interpret(
+        &mut self,
+        ast: &Node,
+        vars: &mut HashMap<Token, Value>,
+    ) -> Result<Value, Error> {
+        self.steps += 1;
+        if let Some(steps) = self.limits.steps.filter(|&steps| self.steps > steps) {
+            return Err(Error::new(
+                ErrorType::LimitError,
+                ast.position().clone(),
+                format!("Exceeded the limit of {} evaluation steps", steps),
+            ));
+        }
+        // Nested calls recurse on the native stack, which is grown on the heap
+        // when it runs low so only `Limits::depth` bounds how deep they go.
+        let value = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.evaluate_node(ast, vars))?;
+        if let Node::Record(..) | Node::Update(..) | Node::Variant(..) = ast {
+            self.allocate(&value, ast.position())?;
+        }
+        Ok(value)
+    }
+
+    fn evaluate_node(
+        &mut self,
+        ast: &Node,
+        vars: &mut HashMap<Token, Value>,
+    ) -> Result<Value, Error> {
+        match ast {
+            Node::Number(n) | Node::Str(n) | Node::Bool(n) => Ok(Value::from_token(n)),
+            Node::Call(name, args, _, written) => {
+                let args = args
+                    .iter()
+                    .map(|a| self.inner_interpret(a, vars))
+                    .collect::<Vec<_>>();
+                let mut new_args = Vec::with_capacity(args.capacity());
+                for arg in args {
+                    new_args.push(arg?);
                 }
-                let mut ret = Value::None;
-                for statement in &func.body().clone() {
-                    ret = inner_interpret(statement, &mut new)?;
+                let ret = match self.lookup(vars, name) {
+                    Some(Value::Closure(func, frame)) => {
+                        let new = Frame::new(frame);
+                        for ((_, p), a) in func.params().iter().zip(new_args) {
+                            new.vars.borrow_mut().insert(p.clone(), a);
+                        }
+                        if let Some(depth) = self.limits.depth.filter(|&depth| self.depth >= depth)
+                        {
+                            return Err(Error::new(
+                                ErrorType::LimitError,
+                                name.position().clone(),
+                                format!("Exceeded the maximum call depth of {}", depth),
+                            ));
+                        }
+                        self.check_cancelled(name.position())?;
+                        self.depth += 1;
+                        let ret = self.enter(new, func.body(), vars);
+                        self.depth -= 1;
+                        ret.map_err(|mut err| {
+                            err.push_frame(written.clone(), name.position().clone());
+                            err
+                        })?
+                    }
+                    Some(Value::FuncAccess(name)) => {
+                        let value = self.builtin(&name, &new_args)?;
+                        self.allocate(&value, name.position())?;
+                        value
+                    }
+                    Some(_) => unreachable!(),
+                    None => {
+                        let value = self.builtin(name, &new_args)?;
+                        self.allocate(&value, name.position())?;
+                        value
+                    }
+                };
+                Ok(ret)
+            }
+            Node::Define(t, func) => {
+                let value = match &**func {
+                    Node::Function(f, _) => Value::Function(f.clone()),
+                    func => self.inner_interpret(func, vars)?,
+                };
+                self.define(vars, t, value);
+                Ok(Value::None)
+            }
+            Node::Let(t, value, _, false) => {
+                let value = self.inner_interpret(value, vars)?;
+                self.define(vars, t, value);
+                Ok(Value::None)
+            }
+            Node::Let(t, value, _, true) => {
+                let value = self.inner_interpret(value, vars)?;
+                self.define(vars, t, Value::Mutable(Rc::new(RefCell::new(value))));
+                Ok(Value::None)
+            }
+            Node::Assign(t, value) => {
+                let value = self.inner_interpret(value, vars)?;
+                match self.find(vars, t, |value, _| value.clone()) {
+                    Some(Value::Mutable(cell)) => *cell.borrow_mut() = value,
+                    _ => unreachable!(),
                 }
-                ret
+                Ok(Value::None)
+            }
+            Node::Statements(statements, ..) => {
+                self.enter(Frame::new(self.frame.clone()), statements, vars)
+            }
+            Node::Function(f, _) => Ok(Value::Closure(f.clone(), self.frame.clone())),
+            Node::FuncAccess(func, _, _) => Ok(match self.lookup(vars, func) {
+                Some(func @ (Value::Closure(..) | Value::FuncAccess(_))) => func,
+                Some(_) => unreachable!(),
+                None => Value::FuncAccess(func.clone()),
+            }),
+            Node::Var(t, _) => Ok(if let Some(var) = self.lookup(vars, t) {
+                var
             } else {
-                get_func(func)(new_args.as_slice())?
-            };
-            Ok(ret)
+                unreachable!();
+            }),
+            Node::TypeDef(..) | Node::Import(..) => Ok(Value::None),
+            Node::While(condition, body, _) => {
+                while self.inner_interpret(condition, vars)? == Value::Bool(true) {
+                    self.check_cancelled(ast.position())?;
+                    self.inner_interpret(body, vars)?;
+                }
+                Ok(Value::None)
+            }
+            Node::For(var, start, end, body, _) => {
+                let start = self.inner_interpret(start, vars)?.get_number();
+                let end = self.inner_interpret(end, vars)?.get_number();
+                // Each iteration runs the body in a single frame that also
+                // holds the loop variable.
+                let body = match &**body {
+                    Node::Statements(statements, ..) => statements,
+                    body => std::slice::from_ref(body),
+                };
+                let mut i = start;
+                while i < end {
+                    self.check_cancelled(ast.position())?;
+                    let new = Frame::new(self.frame.clone());
+                    new.vars.borrow_mut().insert(var.clone(), Value::Number(i));
+                    self.enter(new, body, vars)?;
+                    i += 1.;
+                }
+                Ok(Value::None)
+            }
+            Node::Try(body, fallback, ..) => match (self.inner_interpret(body, vars), fallback) {
+                // The host stopped the script, which `try` must not undo.
+                (Err(err), _)
+                    if matches!(
+                        err.error_type(),
+                        ErrorType::Cancelled | ErrorType::LimitError
+                    ) =>
+                {
+                    Err(err)
+                }
+                (Ok(value), Some(_)) => Ok(value),
+                (Ok(value), None) => Ok(Value::Variant("Ok".to_string(), vec![value])),
+                (Err(_), Some(fallback)) => self.inner_interpret(fallback, vars),
+                (Err(err), None) => Ok(Value::Variant(
+                    "Err".to_string(),
+                    vec![Value::Str(err.details().to_string())],
+                )),
+            },
+            Node::Record(name, fields, _) => {
+                let mut values = Vec::with_capacity(fields.len());
+                for (field, node) in fields {
+                    values.push((field.to_string(), self.inner_interpret(node, vars)?));
+                }
+                Ok(Value::Record(name.to_string(), values))
+            }
+            Node::Field(field, record, _) => match self.inner_interpret(record, vars)? {
+                Value::Record(_, values) => Ok(values
+                    .into_iter()
+                    .find(|(name, _)| **field == TokenType::Field(name.clone()))
+                    .map(|(_, value)| value)
+                    .unwrap()),
+                _ => unreachable!(),
+            },
+            Node::Update(record, fields, ..) => match self.inner_interpret(record, vars)? {
+                Value::Record(name, mut values) => {
+                    for (field, node) in fields {
+                        let value = self.inner_interpret(node, vars)?;
+                        if let Some((_, v)) = values.iter_mut().find(|(name, _)| **field == &**name)
+                        {
+                            *v = value;
+                        }
+                    }
+                    Ok(Value::Record(name, values))
+                }
+                _ => unreachable!(),
+            },
+            Node::Variant(name, args, _) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.inner_interpret(arg, vars)?);
+                }
+                Ok(Value::Variant(name.to_string(), values))
+            }
+            Node::Match(target, arms, ..) => {
+                let (variant, values) = match self.inner_interpret(target, vars)? {
+                    Value::Variant(variant, values) => (variant, values),
+                    _ => unreachable!(),
+                };
+                for (pattern, body) in arms {
+                    match pattern {
+                        Pattern::Variant(name, binders) if **name == &*variant => {
+                            let new = Frame::new(self.frame.clone());
+                            for (binder, value) in binders.iter().zip(values) {
+                                new.vars.borrow_mut().insert(binder.clone(), value);
+                            }
+                            return self.enter(new, std::slice::from_ref(body), vars);
+                        }
+                        Pattern::Wildcard(_) => return self.inner_interpret(body, vars),
+                        _ => {}
+                    }
+                }
+                unreachable!()
+            }
+            Node::If(cond, then, else_, ..) => {
+                if match self.inner_interpret(cond, vars)? {
+                    Value::Bool(b) => b,
+                    _ => unreachable!(),
+                } {
+                    self.inner_interpret(then, vars)
+                } else {
+                    self.inner_interpret(else_, vars)
+                }
+            }
         }
-        Node::Define(t, func) => {
-            let node = inner_interpret(func, vars)?;
-            vars.insert(t.clone(), node);
-            Ok(Value::None)
+    }
+
+    // Runs statements in a new frame, and returns the value of the last one.
+    fn enter(
+        &mut self,
+        frame: Frame,
+        statements: &[Node],
+        vars: &mut HashMap<Token, Value>,
+    ) -> Result<Value, Error> {
+        hoist(statements, &mut frame.vars.borrow_mut());
+        let outer = self.frame.replace(Rc::new(frame));
+        let mut ret = Ok(Value::None);
+        for statement in statements {
+            ret = self.inner_interpret(statement, vars);
+            if ret.is_err() {
+                break;
+            }
         }
-        Node::Statements(statements, ..) => {
-            let mut new = vars.clone();
-            let mut ret = Value::None;
-            for statement in statements {
-                ret = inner_interpret(statement, &mut new)?;
+        self.frame = outer;
+        ret
+    }
+
+    // Calls `found` with the innermost binding of `name` and the frame it is
+    // in, which is `None` for the top level.
+    fn find<T>(
+        &self,
+        vars: &HashMap<Token, Value>,
+        name: &Token,
+        found: impl FnOnce(&Value, Option<&Rc<Frame>>) -> T,
+    ) -> Option<T> {
+        let mut frame = self.frame.as_ref();
+        while let Some(current) = frame {
+            if let Some(value) = current.vars.borrow().get(name) {
+                return Some(found(value, Some(current)));
             }
-            Ok(ret)
+            frame = current.parent.as_ref();
         }
-        Node::Function(f, _) => Ok(Value::Function(f.clone())),
-        Node::FuncAccess(func, _, _) => Ok(if let Some(func) = vars.get(func) {
-            if let Value::Function(_) = func {
-                func.clone()
-            } else {
-                unreachable!();
+        vars.get(name).map(|value| found(value, None))
+    }
+
+    // Functions are stored without a frame, as theirs is the one they are
+    // stored in.
+    fn lookup(&self, vars: &HashMap<Token, Value>, name: &Token) -> Option<Value> {
+        self.find(vars, name, |value, frame| match value {
+            Value::Function(f) => Value::Closure(f.clone(), frame.cloned()),
+            Value::Mutable(cell) => cell.borrow().clone(),
+            value => value.clone(),
+        })
+    }
+
+    fn define(&self, vars: &mut HashMap<Token, Value>, name: &Token, value: Value) {
+        match &self.frame {
+            Some(frame) => frame.vars.borrow_mut().insert(name.clone(), value),
+            None => vars.insert(name.clone(), value),
+        };
+    }
+
+    fn check_cancelled(&self, pos: &Position) -> Result<(), Error> {
+        if self.cancellation.is_cancelled() {
+            return Err(Error::new(
+                ErrorType::Cancelled,
+                pos.clone(),
+                "Execution was cancelled".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn allocate(&mut self, value: &Value, pos: &Position) -> Result<(), Error> {
+        if let Value::Str(_) | Value::Record(..) | Value::Variant(..) = value {
+            self.allocated += value.size();
+        }
+        match self.limits.memory {
+            Some(memory) if self.allocated > memory => Err(Error::new(
+                ErrorType::LimitError,
+                pos.clone(),
+                format!("Exceeded the memory limit of {} bytes", memory),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn builtin(&mut self, name: &Token, args: &[Value]) -> Result<Value, Error> {
+        let io_error = |path: &str, err: io::Error| {
+            Error::new(
+                ErrorType::IoError,
+                name.position().clone(),
+                format!("{} ~> {}", path, err),
+            )
+        };
+        match (name.to_string().as_str(), args) {
+            (".", [value]) => self
+                .output
+                .write(&format!("{}\n", value))
+                .map(|_| Value::None)
+                .map_err(|err| io_error("<stdout>", err)),
+            ("print", [value]) => self
+                .output
+                .write(&value.to_string())
+                .map(|_| Value::None)
+                .map_err(|err| io_error("<stdout>", err)),
+            ("read_line", []) => {
+                let mut line = String::new();
+                match io::stdin().read_line(&mut line) {
+                    Ok(0) => Ok(Value::Variant("None".to_string(), vec![])),
+                    Ok(_) => {
+                        let len = line.trim_end_matches(['\n', '\r']).len();
+                        line.truncate(len);
+                        Ok(Value::Variant("Some".to_string(), vec![Value::Str(line)]))
+                    }
+                    Err(err) => Err(io_error("<stdin>", err)),
+                }
             }
-        } else {
-            Value::FuncAccess(func.clone())
-        }),
-        Node::Var(t, _) => Ok(if let Some(var) = vars.get(t) {
-            var.clone()
-        } else {
-            unreachable!();
-        }),
-        Node::If(cond, then, else_, _) => {
-            if match inner_interpret(cond, vars)? {
-                Value::Bool(b) => b,
-                _ => unreachable!(),
-            } {
-                inner_interpret(then, vars)
-            } else {
-                inner_interpret(else_, vars)
+            ("read_file", [Value::Str(path)]) => fs::read_to_string(path)
+                .map(Value::Str)
+                .map_err(|err| io_error(path, err)),
+            ("write_file", [Value::Str(path), Value::Str(contents)]) => fs::write(path, contents)
+                .map(|_| Value::None)
+                .map_err(|err| io_error(path, err)),
+            ("append_file", [Value::Str(path), Value::Str(contents)]) => OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(contents.as_bytes()))
+                .map(|_| Value::None)
+                .map_err(|err| io_error(path, err)),
+            ("env_var", [Value::Str(name)]) => Ok(match env::var(name) {
+                Ok(value) => Value::Variant("Some".to_string(), vec![Value::Str(value)]),
+                Err(_) => Value::Variant("None".to_string(), vec![]),
+            }),
+            ("clock", []) => Ok(Value::Number(
+                (SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis()) as f64)
+                    .into(),
+            )),
+            ("sleep", [ms]) => {
+                thread::sleep(Duration::from_millis(
+                    ms.get_number().into_inner().max(0.) as u64
+                ));
+                Ok(Value::None)
+            }
+            ("args", []) => Ok(self
+                .args
+                .iter()
+                .rev()
+                .fold(Value::Variant("Nil".to_string(), vec![]), |rest, arg| {
+                    Value::Variant("Cons".to_string(), vec![Value::Str(arg.clone()), rest])
+                })),
+            _ => get_func(name)(args),
+        }
+    }
+}
+
+pub fn hoist(statements: &[Node], vars: &mut HashMap<Token, Value>) {
+    let mut seen = vec![];
+    for statement in statements {
+        if let Node::Define(t, node) = statement {
+            if seen.contains(&t) {
+                continue;
+            }
+            seen.push(t);
+            if let Node::Function(f, _) = &**node {
+                vars.insert(t.clone(), Value::Function(f.clone()));
             }
         }
     }
 }
 
-fn get_func(name: &Token) -> Box<dyn FnOnce(&[Value]) -> Result<Value, Error>> {
+fn get_func(name: &Token) -> BuiltIn {
     let pos = name.position().clone();
     match **name {
         TokenType::Word(ref func) => match func.as_str() {
@@ -124,16 +539,79 @@ fn get_func(name: &Token) -> Box<dyn FnOnce(&[Value]) -> Result<Value, Error>> {
                     Ok(Value::Number(a.get_number() / b.get_number()))
                 }
             }),
-            "." => Box::new(|a| {
-                println!(
-                    "{}",
-                    match a {
-                        [a] => a,
+            "%" => Box::new(move |a| {
+                let (a, b) = match a {
+                    [a, b] => (a.get_number(), b.get_number()),
+                    _ => unreachable!(),
+                };
+                if b == 0. {
+                    Err(Error::new(
+                        ErrorType::DivisionByZero,
+                        pos,
+                        "Cannot divide by zero".to_string(),
+                    ))
+                } else {
+                    Ok(Value::Number(a % b))
+                }
+            }),
+            "pow" | "min" | "max" => {
+                let op = func.clone();
+                Box::new(move |a| {
+                    let (a, b) = match a {
+                        [a, b] => (a.get_number().0, b.get_number().0),
                         _ => unreachable!(),
+                    };
+                    let result = match op.as_str() {
+                        "pow" => a.powf(b),
+                        "min" => a.min(b),
+                        _ => a.max(b),
+                    };
+                    if !(result.is_nan() || op == "pow" && a == 0. && b < 0.) {
+                        Ok(Value::Number(result.into()))
+                    } else {
+                        Err(Error::new(
+                            ErrorType::DomainError,
+                            pos,
+                            format!("{} is not defined for {} and {}", op, a, b),
+                        ))
                     }
-                );
-                Ok(Value::None)
-            }),
+                })
+            }
+            "abs" | "floor" | "ceil" | "round" | "sqrt" | "exp" | "ln" | "sin" | "cos" | "tan"
+            | "asin" | "acos" | "atan" | "neg" => {
+                let op = func.clone();
+                Box::new(move |a| {
+                    let x = match a {
+                        [a] => a.get_number().0,
+                        _ => unreachable!(),
+                    };
+                    let result = match op.as_str() {
+                        "abs" => x.abs(),
+                        "floor" => x.floor(),
+                        "ceil" => x.ceil(),
+                        "round" => x.round(),
+                        "sqrt" => x.sqrt(),
+                        "exp" => x.exp(),
+                        "ln" => x.ln(),
+                        "sin" => x.sin(),
+                        "cos" => x.cos(),
+                        "tan" => x.tan(),
+                        "asin" => x.asin(),
+                        "acos" => x.acos(),
+                        "atan" => x.atan(),
+                        _ => -x,
+                    };
+                    if !(result.is_nan() || op == "ln" && x == 0.) {
+                        Ok(Value::Number(result.into()))
+                    } else {
+                        Err(Error::new(
+                            ErrorType::DomainError,
+                            pos,
+                            format!("{} is not defined for {}", op, x),
+                        ))
+                    }
+                })
+            }
             "?" => Box::new(|a| {
                 let (&a, b, c) = match a {
                     [Value::Bool(a), b, c] => (a, b.clone(), c.clone()),
@@ -152,6 +630,87 @@ fn get_func(name: &Token) -> Box<dyn FnOnce(&[Value]) -> Result<Value, Error>> {
                 };
                 Ok(Value::Bool(a == b))
             }),
+            "<" | ">" | "<=" | ">=" => {
+                let op = func.clone();
+                Box::new(move |a| {
+                    let (a, b) = match a {
+                        [a, b] => (a.get_number(), b.get_number()),
+                        _ => unreachable!(),
+                    };
+                    Ok(Value::Bool(match op.as_str() {
+                        "<" => a < b,
+                        ">" => a > b,
+                        "<=" => a <= b,
+                        _ => a >= b,
+                    }))
+                })
+            }
+            "assert" => Box::new(move |a| match a {
+                [Value::Bool(true)] => Ok(Value::None),
+                [Value::Bool(false)] => Err(Error::new(
+                    ErrorType::AssertionError,
+                    pos,
+                    "Assertion failed".to_string(),
+                )),
+                _ => unreachable!(),
+            }),
+            "assert_eq" => Box::new(move |a| match a {
+                [actual, expected] if actual == expected => Ok(Value::None),
+                [actual, expected] => Err(Error::new(
+                    ErrorType::AssertionError,
+                    pos,
+                    format!("Expected {}, but got {}", expected, actual),
+                )),
+                _ => unreachable!(),
+            }),
+            "fail" => Box::new(move |a| match a {
+                [Value::Str(message)] => {
+                    Err(Error::new(ErrorType::UserError, pos, message.clone()))
+                }
+                _ => unreachable!(),
+            }),
+            "concat" => Box::new(|a| match a {
+                [Value::Str(a), Value::Str(b)] => Ok(Value::Str(format!("{}{}", a, b))),
+                _ => unreachable!(),
+            }),
+            "to_str" => Box::new(|a| match a {
+                [a] => Ok(Value::Str(a.to_string())),
+                _ => unreachable!(),
+            }),
+            "str_len" => Box::new(|a| match a {
+                [Value::Str(s)] => Ok(Value::Number((s.chars().count() as f64).into())),
+                _ => unreachable!(),
+            }),
+            "div" => Box::new(|a| {
+                let (a, b) = match a {
+                    [a, b] => (a, b),
+                    _ => unreachable!(),
+                };
+                Ok(if b.get_number() == 0. {
+                    Value::Variant(
+                        "Err".to_string(),
+                        vec![Value::Str("Cannot divide by zero".to_string())],
+                    )
+                } else {
+                    Value::Variant(
+                        "Ok".to_string(),
+                        vec![Value::Number(a.get_number() / b.get_number())],
+                    )
+                })
+            }),
+            "parse" => Box::new(|a| {
+                let s = match a {
+                    [Value::Str(s)] => s,
+                    _ => unreachable!(),
+                };
+                Ok(match s.trim().parse::<f64>() {
+                    Ok(n) => Value::Variant("Ok".to_string(), vec![Value::Number(n.into())]),
+                    Err(_) => Value::Variant(
+                        "Err".to_string(),
+                        vec![Value::Str(format!("Cannot parse {:?} as a number", s))],
+                    ),
+                })
+            }),
             _ => unreachable!("Function : {name} not implemented"),
         },
         _ => unreachable!(),
@@ -1,13 +1,31 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
+    error::{Error, ErrorType, Position},
     token::{Token, TokenType},
     KEYWORDS,
 };
 
-pub fn lex(code: &str, file: String) -> Vec<Token> {
-    let file = Rc::new(file);
+pub fn lex(code: &str, file: String) -> Result<Vec<Token>, Error> {
+    let (tokens, error) = scan(code, file);
+    match error {
+        Some(error) => Err(error),
+        None => Ok(tokens
+            .into_iter()
+            .filter(|token| !matches!(**token, TokenType::Comment(_)))
+            .collect()),
+    }
+}
+
+// Keeps going past errors, for tools that work on files being edited.
+pub fn lex_with_comments(code: &str, file: String) -> Vec<Token> {
+    scan(code, file).0
+}
+
+fn scan(code: &str, file: String) -> (Vec<Token>, Option<Error>) {
+    let file = Arc::new(file);
     let mut tokens = Vec::new();
+    let mut error = None;
     let mut last_line = 0;
     let mut line = 1;
     let mut chars = code.chars().enumerate().peekable();
@@ -18,13 +36,33 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                 line += 1;
             }
             ' ' | '\t' | '\r' => {}
+            '#' => {
+                let mut comment = c.to_string();
+                let mut end = i + 1;
+                while let Some(&(i, c)) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    comment.push(c);
+                    end = i + 1;
+                    chars.next();
+                }
+                tokens.push(Token::new(
+                    TokenType::Comment(comment.trim_end().to_string()),
+                    line,
+                    line,
+                    i + 1 - last_line,
+                    end + 1 - last_line,
+                    Arc::clone(&file),
+                ));
+            }
             ':' => tokens.push(Token::new(
                 TokenType::Colon,
                 line,
                 line,
                 i + 1 - last_line,
                 i + 2 - last_line,
-                Rc::clone(&file),
+                Arc::clone(&file),
             )),
             '|' => tokens.push(Token::new(
                 TokenType::Pipe,
@@ -32,7 +70,7 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                 line,
                 i + 1 - last_line,
                 i + 2 - last_line,
-                Rc::clone(&file),
+                Arc::clone(&file),
             )),
             '[' => tokens.push(Token::new(
                 TokenType::LBracket,
@@ -40,7 +78,7 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                 line,
                 i + 1 - last_line,
                 i + 2 - last_line,
-                Rc::clone(&file),
+                Arc::clone(&file),
             )),
             ']' => tokens.push(Token::new(
                 TokenType::RBracket,
@@ -48,7 +86,23 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                 line,
                 i + 1 - last_line,
                 i + 2 - last_line,
-                Rc::clone(&file),
+                Arc::clone(&file),
+            )),
+            '{' => tokens.push(Token::new(
+                TokenType::LBrace,
+                line,
+                line,
+                i + 1 - last_line,
+                i + 2 - last_line,
+                Arc::clone(&file),
+            )),
+            '}' => tokens.push(Token::new(
+                TokenType::RBrace,
+                line,
+                line,
+                i + 1 - last_line,
+                i + 2 - last_line,
+                Arc::clone(&file),
             )),
             '!' => tokens.push(Token::new(
                 TokenType::Bang,
@@ -56,7 +110,7 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                 line,
                 i + 1 - last_line,
                 i + 2 - last_line,
-                Rc::clone(&file),
+                Arc::clone(&file),
             )),
             '?' => tokens.push(Token::new(
                 TokenType::Question,
@@ -64,8 +118,50 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                 line,
                 i + 1 - last_line,
                 i + 2 - last_line,
-                Rc::clone(&file),
+                Arc::clone(&file),
             )),
+            '"' => {
+                let start = (i + 1 - last_line, line);
+                let mut string = String::new();
+                let mut end = i + 1;
+                let mut closed = false;
+                while let Some((i, c)) = chars.next() {
+                    end = i + 1;
+                    match c {
+                        '"' => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, 'n')) => string.push('\n'),
+                            Some((_, 't')) => string.push('\t'),
+                            Some((_, c)) => string.push(c),
+                            None => {}
+                        },
+                        '\n' => {
+                            last_line = i + 1;
+                            line += 1;
+                            string.push(c);
+                        }
+                        _ => string.push(c),
+                    }
+                }
+                if !closed && error.is_none() {
+                    error = Some(Error::new(
+                        ErrorType::SyntaxError,
+                        Position::new(start.1, start.1, start.0, start.0 + 1, Arc::clone(&file)),
+                        "Unterminated string".to_string(),
+                    ));
+                }
+                tokens.push(Token::new(
+                    TokenType::Str(string),
+                    start.1,
+                    line,
+                    start.0,
+                    end + 1 - last_line,
+                    Arc::clone(&file),
+                ));
+            }
             '~' if matches!(chars.peek(), Some((_, '>'))) => {
                 chars.next();
                 tokens.push(Token::new(
@@ -74,7 +170,7 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                     line,
                     i + 1 - last_line,
                     i + 3 - last_line,
-                    Rc::clone(&file),
+                    Arc::clone(&file),
                 ));
             }
             _ => {
@@ -82,7 +178,7 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                 let start = (i + 1 - last_line, line);
                 let mut end = i + 1;
                 while let Some(&(i, c)) = chars.peek() {
-                    if "[]!:?|\n\t\r ".contains(c) {
+                    if "[]{}!:?|#\"\n\t\r ".contains(c) {
                         break;
                     }
                     if c == '~' {
@@ -94,7 +190,7 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                                 line,
                                 i + 1 - last_line,
                                 i + 1 - last_line,
-                                Rc::clone(&file),
+                                Arc::clone(&file),
                             ));
                             word.clear();
                             break;
@@ -109,6 +205,13 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                     tokens.push(Token::new(
                         match word.parse() {
                             Ok(n) => TokenType::Number(n),
+                            _ if word.starts_with('.')
+                                && word[1..]
+                                    .starts_with(|c: char| c.is_alphabetic() || c == '_') =>
+                            {
+                                TokenType::Field(word[1..].to_string())
+                            }
+                            _ if word == "<-" => TokenType::LeftArrow,
                             _ => {
                                 if KEYWORDS.contains(&&*word) {
                                     TokenType::Keyword(word.clone())
@@ -121,12 +224,20 @@ pub fn lex(code: &str, file: String) -> Vec<Token> {
                         line,
                         start.0,
                         end + 1 - last_line,
-                        Rc::clone(&file),
+                        Arc::clone(&file),
                     ))
                 }
             }
         }
     }
-    tokens.push(Token::new(TokenType::Eof, 0, 0, 0, 0, Rc::clone(&file)));
-    tokens
+    let column = code.chars().count() + 1 - last_line;
+    tokens.push(Token::new(
+        TokenType::Eof,
+        line,
+        line,
+        column,
+        column,
+        Arc::clone(&file),
+    ));
+    (tokens, error)
 }
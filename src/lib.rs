@@ -1,36 +1,348 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use ast::Expr;
+use config::Capability::{Clock, Environment, Filesystem, Print, Stdin};
 use functions::{BuiltInFunction, Type};
+use interpreter::Interpreter;
+use loader::Loader;
+use node::Node;
+use relation::Substitution;
+use scope::Scope;
+use token::Token;
+use value::Value;
+
+pub use analysis::{Analysis, Completion, Symbol, SymbolKind};
+pub use config::{CancellationToken, Capability, Config, Limits, Output};
+pub use error::{Error, ErrorType, Position};
+pub use formatter::format;
+pub use highlight::{SemanticKind, SemanticToken};
+pub use lsp::serve_lsp;
+pub use repl::Repl;
 
+mod analysis;
+mod ast;
+mod checker;
+mod config;
 mod error;
+mod formatter;
 mod functions;
+mod highlight;
 mod interpreter;
 mod lexer;
+mod loader;
+mod lsp;
 mod node;
 mod parser;
+mod relation;
+mod repl;
 mod scope;
 mod token;
 mod value;
 
-const KEYWORDS: [&str; 1] = ["Int"];
-static DEFINED_WORDS: [BuiltInFunction; 7] = [
+const KEYWORDS: [&str; 21] = [
+    "Int", "Bool", "Str", "Any", "true", "false", "record", "with", "enum", "match", "try", "else",
+    "let", "mut", "while", "for", "in", "import", "as", "private", "test",
+];
+const CORE: &str = include_str!("core.tru");
+const PRELUDE: &str = include_str!("prelude.tru");
+const RESULT: Type = Type::Named(
+    Cow::Borrowed("Result"),
+    Cow::Borrowed(&[Type::Number, Type::Str]),
+);
+static DEFINED_WORDS: [BuiltInFunction; 46] = [
     BuiltInFunction::new("+", &[Type::Number, Type::Number], Type::Number),
     BuiltInFunction::new("-", &[Type::Number, Type::Number], Type::Number),
     BuiltInFunction::new("*", &[Type::Number, Type::Number], Type::Number),
     BuiltInFunction::new("/", &[Type::Number, Type::Number], Type::Number),
-    BuiltInFunction::new(".", &[Type::Any], Type::None),
-    BuiltInFunction::new("?", &[Type::Bool, Type::Any, Type::Any], Type::Any),
+    BuiltInFunction::new("%", &[Type::Number, Type::Number], Type::Number),
+    BuiltInFunction::new("pow", &[Type::Number, Type::Number], Type::Number),
+    BuiltInFunction::new("min", &[Type::Number, Type::Number], Type::Number),
+    BuiltInFunction::new("max", &[Type::Number, Type::Number], Type::Number),
+    BuiltInFunction::new("abs", &[Type::Number], Type::Number),
+    BuiltInFunction::new("floor", &[Type::Number], Type::Number),
+    BuiltInFunction::new("ceil", &[Type::Number], Type::Number),
+    BuiltInFunction::new("round", &[Type::Number], Type::Number),
+    BuiltInFunction::new("sqrt", &[Type::Number], Type::Number),
+    BuiltInFunction::new("exp", &[Type::Number], Type::Number),
+    BuiltInFunction::new("ln", &[Type::Number], Type::Number),
+    BuiltInFunction::new("sin", &[Type::Number], Type::Number),
+    BuiltInFunction::new("cos", &[Type::Number], Type::Number),
+    BuiltInFunction::new("tan", &[Type::Number], Type::Number),
+    BuiltInFunction::new("asin", &[Type::Number], Type::Number),
+    BuiltInFunction::new("acos", &[Type::Number], Type::Number),
+    BuiltInFunction::new("atan", &[Type::Number], Type::Number),
+    BuiltInFunction::new("neg", &[Type::Number], Type::Number),
+    BuiltInFunction::new(".", &[Type::Var(Cow::Borrowed("T"))], Type::None).requires(Print),
+    BuiltInFunction::new(
+        "?",
+        &[
+            Type::Bool,
+            Type::Var(Cow::Borrowed("T")),
+            Type::Var(Cow::Borrowed("T")),
+        ],
+        Type::Var(Cow::Borrowed("T")),
+    ),
     BuiltInFunction::new("==", &[Type::Number, Type::Number], Type::Bool),
+    BuiltInFunction::new("<", &[Type::Number, Type::Number], Type::Bool),
+    BuiltInFunction::new(">", &[Type::Number, Type::Number], Type::Bool),
+    BuiltInFunction::new("<=", &[Type::Number, Type::Number], Type::Bool),
+    BuiltInFunction::new(">=", &[Type::Number, Type::Number], Type::Bool),
+    BuiltInFunction::new("div", &[Type::Number, Type::Number], RESULT),
+    BuiltInFunction::new("parse", &[Type::Str], RESULT),
+    BuiltInFunction::new("fail", &[Type::Str], Type::Var(Cow::Borrowed("T"))),
+    BuiltInFunction::new("assert", &[Type::Bool], Type::None),
+    BuiltInFunction::new(
+        "assert_eq",
+        &[Type::Var(Cow::Borrowed("T")), Type::Var(Cow::Borrowed("T"))],
+        Type::None,
+    ),
+    BuiltInFunction::new("concat", &[Type::Str, Type::Str], Type::Str),
+    BuiltInFunction::new("to_str", &[Type::Var(Cow::Borrowed("T"))], Type::Str),
+    BuiltInFunction::new("str_len", &[Type::Str], Type::Number),
+    BuiltInFunction::new("print", &[Type::Var(Cow::Borrowed("T"))], Type::None).requires(Print),
+    BuiltInFunction::new(
+        "read_line",
+        &[],
+        Type::Named(Cow::Borrowed("Option"), Cow::Borrowed(&[Type::Str])),
+    )
+    .requires(Stdin),
+    BuiltInFunction::new(
+        "env_var",
+        &[Type::Str],
+        Type::Named(Cow::Borrowed("Option"), Cow::Borrowed(&[Type::Str])),
+    )
+    .requires(Environment),
+    BuiltInFunction::new("clock", &[], Type::Number).requires(Clock),
+    BuiltInFunction::new("sleep", &[Type::Number], Type::None).requires(Clock),
+    BuiltInFunction::new("read_file", &[Type::Str], Type::Str).requires(Filesystem),
+    BuiltInFunction::new("write_file", &[Type::Str, Type::Str], Type::None).requires(Filesystem),
+    BuiltInFunction::new("append_file", &[Type::Str, Type::Str], Type::None).requires(Filesystem),
+    BuiltInFunction::new(
+        "args",
+        &[],
+        Type::Named(Cow::Borrowed("List"), Cow::Borrowed(&[Type::Str])),
+    )
+    .requires(Environment),
 ];
 
-pub fn run(contents: &str, file: &str) -> Result<(), error::Error> {
-    let tokens = lexer::lex(contents, file.to_string());
-    println!(
-        "{:?}",
-        tokens.iter().map(|t| (**t).clone()).collect::<Vec<_>>()
+fn load(
+    source: &str,
+    file: &str,
+    scope: &mut Scope,
+    subst: &mut Substitution,
+    vars: &mut HashMap<Token, Value>,
+    interpreter: &mut Interpreter,
+) {
+    let tokens = lexer::lex(source, file.to_string()).expect("bundled library should lex");
+    scope.set_namespace(Some(file.to_string()));
+    let statements = match parser::parse(&tokens).expect("bundled library should parse") {
+        Expr::Block(exprs, pos) => checker::check_in(
+            &exprs,
+            &pos,
+            scope,
+            subst,
+            &mut Loader::new(Scope::new()),
+            &Capability::ALL,
+        )
+        .expect("bundled library should type check"),
+        _ => unreachable!(),
+    };
+    scope.set_namespace(None);
+    interpreter
+        .execute(&statements, vars)
+        .expect("bundled library should evaluate");
+}
+
+fn environment(config: &Config) -> (Scope, Substitution, HashMap<Token, Value>) {
+    let mut scope = Scope::new();
+    let mut subst = Substitution::new();
+    let mut vars = HashMap::new();
+    let mut interpreter = Interpreter::new(config);
+    load(
+        CORE,
+        "<core>",
+        &mut scope,
+        &mut subst,
+        &mut vars,
+        &mut interpreter,
     );
-    println!("----------------------------------------------------------------------");
-    let ast = parser::parse(&tokens)?;
-    println!("----------------------------------------------------------------------");
-    println!("{ast}");
-    println!("----------------------------------------------------------------------");
-    interpreter::interpret(&ast)
+    if config.prelude {
+        load(
+            PRELUDE,
+            "<prelude>",
+            &mut scope,
+            &mut subst,
+            &mut vars,
+            &mut interpreter,
+        );
+    }
+    (scope, subst, vars)
+}
+
+pub fn run(contents: &str, file: &str) -> Result<(), error::Error> {
+    run_with(contents, file, &Config::new())
+}
+
+pub fn run_with(contents: &str, file: &str, config: &Config) -> Result<(), error::Error> {
+    let (mut scope, mut subst, mut vars) = environment(config);
+    let mut loader = Loader::new(scope.clone());
+    loader.enter(file);
+    let tokens = lexer::lex(contents, file.to_string())?;
+    let ast = checker::check(
+        &parser::parse(&tokens)?,
+        &mut scope,
+        &mut subst,
+        &mut loader,
+        &config.capabilities,
+    )?;
+    let mut interpreter = Interpreter::new(config);
+    for module in loader.modules() {
+        interpreter.execute(module.statements(), &mut vars)?;
+    }
+    interpreter.interpret(&ast, &mut vars)
+}
+
+#[derive(Debug)]
+pub struct TestResult {
+    pub name: String,
+    pub error: Option<error::Error>,
+}
+
+pub fn run_tests(contents: &str, file: &str) -> Result<Vec<TestResult>, error::Error> {
+    run_tests_with(contents, file, &Config::new())
+}
+
+// Each test gets a fresh copy of the program's definitions, while the
+// program's own top-level expressions are not run at all.
+pub fn run_tests_with(
+    contents: &str,
+    file: &str,
+    config: &Config,
+) -> Result<Vec<TestResult>, error::Error> {
+    let (mut scope, mut subst, vars) = environment(config);
+    let mut loader = Loader::new(scope.clone());
+    loader.enter(file);
+    let tokens = lexer::lex(contents, file.to_string())?;
+    let ast = checker::check(
+        &parser::parse(&tokens)?,
+        &mut scope,
+        &mut subst,
+        &mut loader,
+        &config.capabilities,
+    )?;
+    let definitions = match ast {
+        Node::Statements(statements, ..) => statements
+            .into_iter()
+            .filter(|s| {
+                matches!(
+                    s,
+                    Node::Define(..) | Node::Let(..) | Node::TypeDef(..) | Node::Import(..)
+                )
+            })
+            .collect(),
+        _ => vec![],
+    };
+    Ok(scope
+        .tests()
+        .iter()
+        .map(|test| {
+            let mut vars = vars.clone();
+            let mut interpreter = Interpreter::new(config);
+            let result = loader
+                .modules()
+                .iter()
+                .try_for_each(|module| interpreter.execute(module.statements(), &mut vars))
+                .and_then(|_| interpreter.execute(&definitions, &mut vars))
+                .and_then(|_| {
+                    interpreter.interpret(
+                        &Node::Call(test.clone(), vec![], Type::None, test.to_string()),
+                        &mut vars,
+                    )
+                });
+            TestResult {
+                name: test.to_string(),
+                error: result.err(),
+            }
+        })
+        .collect())
+}
+
+// Checks a program without running it, and finds the type and definition of
+// every name in it, for editors.
+pub fn analyze(contents: &str, file: &str, config: &Config) -> Analysis {
+    let (mut scope, mut subst, _) = environment(config);
+    let mut loader = Loader::new(scope.clone());
+    loader.enter(file);
+    let (ast, error) =
+        match lexer::lex(contents, file.to_string()).and_then(|tokens| parser::parse(&tokens)) {
+            Ok(Expr::Block(exprs, pos)) => {
+                let (ast, error) = checker::check_partial(
+                    &exprs,
+                    &pos,
+                    &mut scope,
+                    &mut subst,
+                    &mut loader,
+                    &config.capabilities,
+                );
+                (Some(ast), error)
+            }
+            Ok(_) => unreachable!(),
+            Err(err) => (None, Some(err)),
+        };
+    analysis::walk(
+        ast,
+        error,
+        &scope,
+        &subst,
+        loader.modules().iter().flat_map(|m| m.statements()),
+        DEFINED_WORDS.iter().filter(|f| {
+            f.capability()
+                .is_none_or(|capability| config.capabilities.contains(&capability))
+        }),
+    )
+}
+
+pub fn semantic_tokens(contents: &str, file: &str, config: &Config) -> Vec<SemanticToken> {
+    highlight::classify(
+        &lexer::lex_with_comments(contents, file.to_string()),
+        &analyze(contents, file, config),
+    )
+}
+
+pub fn highlight_html(contents: &str, file: &str, config: &Config) -> String {
+    highlight::html(contents, &semantic_tokens(contents, file, config))
+}
+
+pub fn check(contents: &str, file: &str) -> Result<Vec<String>, error::Error> {
+    check_with(contents, file, &Config::new())
+}
+
+pub fn check_with(
+    contents: &str,
+    file: &str,
+    config: &Config,
+) -> Result<Vec<String>, error::Error> {
+    let (mut scope, mut subst, _) = environment(config);
+    let mut loader = Loader::new(scope.clone());
+    loader.enter(file);
+    let tokens = lexer::lex(contents, file.to_string())?;
+    let ast = checker::check(
+        &parser::parse(&tokens)?,
+        &mut scope,
+        &mut subst,
+        &mut loader,
+        &config.capabilities,
+    )?;
+    Ok(match ast {
+        Node::Statements(statements, ..) => statements
+            .iter()
+            .filter_map(|s| match s {
+                Node::Define(t, node) => Some(format!("{} : {}", t, node.get_type())),
+                Node::Let(t, _, ty, false) => Some(format!("let {} : {}", t, ty)),
+                Node::Let(t, _, ty, true) => Some(format!("let mut {} : {}", t, ty)),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    })
 }
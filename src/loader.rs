@@ -0,0 +1,190 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    config::Capability,
+    error::{Error, ErrorType, Position},
+    functions::TypeDef,
+    node::Node,
+    scope::{Export, Scope},
+    token::Token,
+};
+
+#[derive(Debug)]
+pub struct Module {
+    path: PathBuf,
+    exports: Vec<Export>,
+    types: Vec<(Token, TypeDef)>,
+    statements: Vec<Node>,
+}
+
+impl Module {
+    pub fn exports(&self) -> &[Export] {
+        &self.exports
+    }
+
+    pub fn types(&self) -> &[(Token, TypeDef)] {
+        &self.types
+    }
+
+    pub fn statements(&self) -> &[Node] {
+        &self.statements
+    }
+}
+
+// Modules are kept in the order they finished loading, so every module
+// comes after the modules it imports.
+#[derive(Debug)]
+pub struct Loader {
+    base: Scope,
+    entry: Option<PathBuf>,
+    modules: Vec<Rc<Module>>,
+    loading: Vec<PathBuf>,
+    namespaces: Vec<String>,
+}
+
+impl Loader {
+    pub fn new(base: Scope) -> Loader {
+        Loader {
+            base,
+            entry: None,
+            modules: Vec::new(),
+            loading: Vec::new(),
+            namespaces: Vec::new(),
+        }
+    }
+
+    pub fn enter(&mut self, file: &str) {
+        let path = Path::new(file);
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.entry = Some(path.clone());
+        self.loading.push(path);
+    }
+
+    pub fn modules(&self) -> &[Rc<Module>] {
+        &self.modules
+    }
+
+    // Imports name a `.tru` file relative to the importing file. Without the
+    // filesystem capability, the file also has to be under the directory of
+    // the entry file, so a sandboxed script can't read anything else.
+    pub fn resolve(
+        &self,
+        path: &str,
+        from: &Position,
+        capabilities: &[Capability],
+    ) -> Result<PathBuf, Error> {
+        if Path::new(path).extension() != Some("tru".as_ref()) {
+            return Err(Error::new(
+                ErrorType::ImportError,
+                from.clone(),
+                format!("Only .tru files can be imported, not {}", path),
+            ));
+        }
+        if Path::new(path).is_absolute() {
+            return Err(Error::new(
+                ErrorType::ImportError,
+                from.clone(),
+                format!(
+                    "Imports are relative to the importing file, so {} can't be absolute",
+                    path
+                ),
+            ));
+        }
+        let base = Path::new(from.file())
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let path = base.join(path);
+        let Ok(resolved) = path.canonicalize() else {
+            return Ok(path);
+        };
+        let root = self.root();
+        if !capabilities.contains(&Capability::Filesystem) && !resolved.starts_with(&root) {
+            return Err(Error::new(
+                ErrorType::CapabilityError,
+                from.clone(),
+                format!(
+                    "Importing {} from outside {} needs the {} capability, which has not been granted",
+                    resolved.display(),
+                    root.display(),
+                    Capability::Filesystem
+                ),
+            ));
+        }
+        Ok(resolved)
+    }
+
+    // The directory of the entry file, or the current directory in the REPL.
+    fn root(&self) -> PathBuf {
+        let dir = match self.entry.as_deref().and_then(Path::parent) {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf())
+    }
+
+    pub fn find(&self, path: &Path) -> Option<Rc<Module>> {
+        self.modules.iter().find(|m| m.path == path).cloned()
+    }
+
+    pub fn start(&mut self, path: &Path, pos: &Position) -> Result<(String, Scope), Error> {
+        if self.loading.iter().any(|p| p == path) {
+            return Err(Error::new(
+                ErrorType::ImportError,
+                pos.clone(),
+                format!("Circular import of {}", path.display()),
+            ));
+        }
+        let contents = fs::read_to_string(path).map_err(|err| {
+            Error::new(
+                ErrorType::ImportError,
+                pos.clone(),
+                format!("Could not read {} ~> {}", path.display(), err),
+            )
+        })?;
+        self.loading.push(path.to_path_buf());
+        let mut scope = self.base.get_new();
+        scope.set_namespace(Some(self.namespace(path)));
+        Ok((contents, scope))
+    }
+
+    pub fn finish(
+        &mut self,
+        path: &Path,
+        exports: Vec<Export>,
+        scope: &Scope,
+        statements: Vec<Node>,
+    ) -> Rc<Module> {
+        self.loading.retain(|p| p != path);
+        let module = Rc::new(Module {
+            path: path.to_path_buf(),
+            exports,
+            types: scope.types().to_vec(),
+            statements,
+        });
+        self.modules.push(Rc::clone(&module));
+        module
+    }
+
+    pub fn abort(&mut self, path: &Path) {
+        self.loading.retain(|p| p != path);
+    }
+
+    fn namespace(&mut self, path: &Path) -> String {
+        let stem = path.file_stem().map_or_else(
+            || "module".to_string(),
+            |s| s.to_string_lossy().into_owned(),
+        );
+        let mut namespace = stem.clone();
+        let mut n = 1;
+        while self.namespaces.contains(&namespace) {
+            n += 1;
+            namespace = format!("{}{}", stem, n);
+        }
+        self.namespaces.push(namespace.clone());
+        namespace
+    }
+}
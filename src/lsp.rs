@@ -0,0 +1,407 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    analysis::{Analysis, SymbolKind},
+    config::Config,
+    error::Position,
+    highlight::SemanticKind,
+};
+
+// A language server speaking JSON-RPC with `Content-Length` headers, as
+// editors do over stdio. Every request re-analyzes the whole document. A
+// message that can't be parsed is answered with a parse error, and the server
+// only stops at the end of its input or when reading or writing fails.
+pub fn serve_lsp(mut input: impl BufRead, output: impl Write, config: &Config) -> io::Result<()> {
+    let mut server = Server {
+        output,
+        config,
+        documents: HashMap::new(),
+        utf16: true,
+    };
+    while let Some(message) = read_message(&mut input)? {
+        let message = match message {
+            Ok(message) => message,
+            Err(details) => {
+                server.send(json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": { "code": -32700, "message": details },
+                }))?;
+                continue;
+            }
+        };
+        if !server.handle(&message)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// A message that can't be parsed comes back as the error to answer it with.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<Value, String>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Ok(Some(Err("Missing Content-Length".to_string())));
+    };
+    let mut body = vec![0; length];
+    match input.read_exact(&mut body) {
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    Ok(Some(
+        serde_json::from_slice(&body).map_err(|err| format!("Invalid JSON ~> {}", err)),
+    ))
+}
+
+struct Server<'a, W> {
+    output: W,
+    config: &'a Config,
+    documents: HashMap<String, String>,
+    // Whether positions are sent in UTF-16 code units, as LSP does unless the
+    // client also accepts UTF-32, which is how our columns are counted.
+    utf16: bool,
+}
+
+impl<W: Write> Server<'_, W> {
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.output.flush()
+    }
+
+    fn handle(&mut self, message: &Value) -> io::Result<bool> {
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let result = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => {
+                self.utf16 = !params["capabilities"]["general"]["positionEncodings"]
+                    .as_array()
+                    .is_some_and(|encodings| encodings.iter().any(|e| e == "utf-32"));
+                json!({
+                "capabilities": {
+                    "positionEncoding": if self.utf16 { "utf-16" } else { "utf-32" },
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                    "semanticTokensProvider": {
+                        "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": ["defaultLibrary"] },
+                        "full": true,
+                    },
+                },
+                "serverInfo": { "name": "trulang" },
+                })
+            }
+            "shutdown" => Value::Null,
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                self.publish(uri)?;
+                return Ok(true);
+            }
+            "textDocument/didChange" => {
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+                self.publish(uri)?;
+                return Ok(true);
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.send(json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": [] },
+                }))?;
+                return Ok(true);
+            }
+            "textDocument/hover" => self.hover(uri, &params["position"]),
+            "textDocument/definition" => self.definition(uri, &params["position"]),
+            "textDocument/completion" => self.completion(uri, &params["position"]),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(uri),
+            method => {
+                if let Some(id) = message.get("id") {
+                    self.send(json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("Unknown method {}", method) },
+                    }))?;
+                }
+                return Ok(true);
+            }
+        };
+        if let Some(id) = message.get("id") {
+            self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }))?;
+        }
+        Ok(true)
+    }
+
+    fn analyze(&self, uri: &str) -> Option<Analysis> {
+        let text = self.documents.get(uri)?;
+        Some(crate::analyze(text, &path(uri), self.config))
+    }
+
+    fn publish(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = match self.analyze(uri).and_then(|analysis| analysis.error) {
+            Some(err) if err.position().file() == path(uri) => vec![json!({
+                "range": self.range(err.position()),
+                "severity": 1,
+                "source": "trulang",
+                "code": format!("{:?}", err.error_type()),
+                "message": err.details(),
+            })],
+            // Errors in imported files are shown at the top of the importer.
+            Some(err) => vec![json!({
+                "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+                "severity": 1,
+                "source": "trulang",
+                "code": format!("{:?}", err.error_type()),
+                "message": err.to_string(),
+            })],
+            None => vec![],
+        };
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    fn hover(&self, uri: &str, position: &Value) -> Value {
+        let (line, column) = self.location(uri, position);
+        self.analyze(uri)
+            .and_then(|analysis| {
+                let symbol = analysis.symbol_at(line, column)?;
+                Some(json!({
+                    "contents": { "kind": "markdown", "value": format!("```\n{}\n```", symbol.ty.as_ref()?) },
+                    "range": self.range(&symbol.position),
+                }))
+            })
+            .unwrap_or(Value::Null)
+    }
+
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        let (line, column) = self.location(uri, position);
+        self.analyze(uri)
+            .and_then(|analysis| {
+                let definition = analysis.symbol_at(line, column)?.definition.clone()?;
+                let target = if definition.file() == path(uri) {
+                    uri.to_string()
+                } else {
+                    format!("file://{}", definition.file())
+                };
+                Some(json!({ "uri": target, "range": self.range(&definition) }))
+            })
+            .unwrap_or(Value::Null)
+    }
+
+    // Tokens are encoded relative to the one before them, and a string that
+    // spans lines is sent as one token per line.
+    fn semantic_tokens(&self, uri: &str) -> Value {
+        let Some(text) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let lines = text
+            .split('\n')
+            .map(|l| l.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut data = Vec::new();
+        let (mut previous_line, mut previous_start) = (0, 0);
+        for token in crate::semantic_tokens(text, &path(uri), self.config) {
+            let pos = &token.position;
+            let first = &lines[pos.line() - 1];
+            let start = first[pos.column() - 1..].iter().collect::<String>();
+            let (kind, library) = token_type(token.kind, &start);
+            for line in pos.line()..=pos.line_end() {
+                let from = if line == pos.line() {
+                    pos.column() - 1
+                } else {
+                    0
+                };
+                let to = if line == pos.line_end() {
+                    pos.column_end() - 1
+                } else {
+                    lines[line - 1].len()
+                };
+                if to <= from {
+                    continue;
+                }
+                let (from, to) = (
+                    self.units(&lines[line - 1][..from]),
+                    self.units(&lines[line - 1][..to]),
+                );
+                let line = line - 1;
+                let delta = if line == previous_line {
+                    from - previous_start
+                } else {
+                    from
+                };
+                data.extend([
+                    line - previous_line,
+                    delta,
+                    to - from,
+                    kind,
+                    library as usize,
+                ]);
+                (previous_line, previous_start) = (line, from);
+            }
+        }
+        json!({ "data": data })
+    }
+
+    // LSP positions count from 0, while ours count from 1.
+    fn location(&self, uri: &str, position: &Value) -> (usize, usize) {
+        let line = position["line"].as_u64().unwrap_or(0) as usize + 1;
+        let mut character = position["character"].as_u64().unwrap_or(0) as usize;
+        let mut column = 1;
+        if let Some(text) = self.documents.get(uri) {
+            for c in text.split('\n').nth(line - 1).unwrap_or_default().chars() {
+                if character == 0 {
+                    break;
+                }
+                character = character.saturating_sub(if self.utf16 { c.len_utf16() } else { 1 });
+                column += 1;
+            }
+        }
+        (line, column + character)
+    }
+
+    fn range(&self, pos: &Position) -> Value {
+        let source = self.source(pos.file());
+        let lines = source.split('\n').collect::<Vec<_>>();
+        let character = |line: usize, column: usize| {
+            let chars = lines
+                .get(line - 1)
+                .map_or_else(Vec::new, |l| l.chars().collect());
+            self.units(&chars[..(column - 1).min(chars.len())])
+                + (column - 1).saturating_sub(chars.len())
+        };
+        json!({
+            "start": { "line": pos.line() - 1, "character": character(pos.line(), pos.column()) },
+            "end": { "line": pos.line_end() - 1, "character": character(pos.line_end(), pos.column_end()) },
+        })
+    }
+
+    // The text of a file, as the editor has it if it is open.
+    fn source(&self, file: &str) -> Cow<'_, str> {
+        match self.documents.iter().find(|(uri, _)| path(uri) == file) {
+            Some((_, text)) => Cow::Borrowed(text),
+            None => Cow::Owned(fs::read_to_string(file).unwrap_or_default()),
+        }
+    }
+
+    // How long some characters are in the negotiated encoding.
+    fn units(&self, chars: &[char]) -> usize {
+        if self.utf16 {
+            chars.iter().map(|c| c.len_utf16()).sum()
+        } else {
+            chars.len()
+        }
+    }
+
+    fn completion(&self, uri: &str, position: &Value) -> Value {
+        let (line, column) = self.location(uri, position);
+        let completions = self
+            .analyze(uri)
+            .map(|analysis| analysis.completions(line, column))
+            .unwrap_or_default();
+        Value::Array(
+            completions
+                .into_iter()
+                .map(|completion| {
+                    json!({
+                        "label": completion.name,
+                        "kind": match completion.kind {
+                            SymbolKind::Builtin | SymbolKind::Function => 3,
+                            SymbolKind::Parameter | SymbolKind::Variable => 6,
+                            SymbolKind::Keyword => 14,
+                        },
+                        "detail": completion.detail,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+const TOKEN_TYPES: [&str; 10] = [
+    "function",
+    "parameter",
+    "variable",
+    "type",
+    "keyword",
+    "string",
+    "number",
+    "property",
+    "operator",
+    "comment",
+];
+
+// The index of the LSP token type, and whether it is from the standard library.
+fn token_type(kind: SemanticKind, text: &str) -> (usize, bool) {
+    let (name, library) = match kind {
+        SemanticKind::Builtin => ("function", true),
+        SemanticKind::Function => ("function", false),
+        SemanticKind::Parameter => ("parameter", false),
+        SemanticKind::Variable => ("variable", false),
+        SemanticKind::Type => ("type", false),
+        SemanticKind::TypeKeyword => ("type", true),
+        SemanticKind::Keyword => ("keyword", false),
+        SemanticKind::Literal if text.starts_with('"') => ("string", false),
+        SemanticKind::Literal => ("number", false),
+        SemanticKind::Field => ("property", false),
+        SemanticKind::Punctuation => ("operator", false),
+        SemanticKind::Comment => ("comment", false),
+    };
+    (
+        TOKEN_TYPES.iter().position(|t| *t == name).unwrap(),
+        library,
+    )
+}
+
+fn path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'%' => {
+                let hex = [bytes.next().unwrap_or(b'0'), bytes.next().unwrap_or(b'0')];
+                let hex = std::str::from_utf8(&hex).unwrap_or("00");
+                decoded.push(u8::from_str_radix(hex, 16).unwrap_or(b'%'));
+            }
+            _ => decoded.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
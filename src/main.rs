@@ -1,6 +1,152 @@
-use std::fs;
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+fn read(file: &str) -> String {
+    fs::read_to_string(file).unwrap_or_else(|err| {
+        eprintln!("Could not read {} ~> {}", file, err);
+        process::exit(1);
+    })
+}
+
+fn repl(config: &trulang::Config) {
+    let mut repl = trulang::Repl::with_config(config);
+    let stdin = io::stdin();
+    print!("> ");
+    io::stdout().flush().unwrap();
+    for line in stdin.lock().lines() {
+        match repl.eval(&line.unwrap()) {
+            Ok(output) => output.iter().for_each(|o| println!("{}", o)),
+            Err(err) => println!("{}", err),
+        }
+        print!("> ");
+        io::stdout().flush().unwrap();
+    }
+}
+
+// Directories are searched for .tru files, but not recursively.
+fn test_files(paths: &[&str]) -> Vec<PathBuf> {
+    let paths = if paths.is_empty() { &["."][..] } else { paths };
+    let mut files = Vec::new();
+    for path in paths.iter().map(Path::new) {
+        if path.is_dir() {
+            let mut found = fs::read_dir(path)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                        .filter(|path| path.extension().is_some_and(|e| e == "tru"))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    files
+}
+
+fn test(paths: &[&str], config: &trulang::Config) {
+    let (mut passed, mut failed) = (0, 0);
+    for file in test_files(paths) {
+        let file = file.to_string_lossy();
+        match trulang::run_tests_with(&read(&file), &file, config) {
+            Ok(results) => {
+                for result in results {
+                    match result.error {
+                        None => {
+                            passed += 1;
+                            println!("test {} in {} ... ok", result.name, file);
+                        }
+                        Some(err) => {
+                            failed += 1;
+                            println!("test {} in {} ... FAILED\n{}", result.name, file, err);
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                failed += 1;
+                println!("{}", err);
+            }
+        }
+    }
+    println!("\n{} passed, {} failed", passed, failed);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn fmt(files: &[&str], check: bool) {
+    let mut failed = false;
+    for &file in files {
+        let source = read(file);
+        match trulang::format(&source, file) {
+            Ok(formatted) if formatted == source => {}
+            Ok(_) if check => {
+                failed = true;
+                println!("{} is not formatted", file);
+            }
+            Ok(formatted) => fs::write(file, formatted).unwrap_or_else(|err| {
+                failed = true;
+                eprintln!("Could not write {} ~> {}", file, err);
+            }),
+            Err(err) => {
+                failed = true;
+                println!("{}", err);
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
 
 fn main() {
-    trulang::run(&fs::read_to_string("std.tru").unwrap(), "std.tru")
-        .unwrap_or_else(|err| println!("{}", err));
+    let mut config = trulang::Config::new();
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    loop {
+        match args.first().map(String::as_str) {
+            Some("--no-prelude") => config.prelude = false,
+            Some("--sandbox") => config.capabilities.clear(),
+            _ => break,
+        }
+        args.remove(0);
+    }
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => repl(&config),
+        ["run", file, ref rest @ ..] => {
+            config.args = rest.iter().map(|arg| arg.to_string()).collect();
+            trulang::run_with(&read(file), file, &config).unwrap_or_else(|err| println!("{}", err))
+        }
+        ["test", ref paths @ ..] => test(paths, &config),
+        ["highlight", file] => print!("{}", trulang::highlight_html(&read(file), file, &config)),
+        ["lsp"] => trulang::serve_lsp(io::stdin().lock(), io::stdout().lock(), &config)
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            }),
+        ["fmt", "--check", ref files @ ..] if !files.is_empty() => fmt(files, true),
+        ["fmt", ref files @ ..] if !files.is_empty() => fmt(files, false),
+        [file] if file != "check" => {
+            trulang::run_with(&read(file), file, &config).unwrap_or_else(|err| println!("{}", err))
+        }
+        ["check", file] => match trulang::check_with(&read(file), file, &config) {
+            Ok(signatures) => signatures.iter().for_each(|s| println!("{}", s)),
+            Err(err) => {
+                println!("{}", err);
+                process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!(
+                "Usage: trulang [--no-prelude] [--sandbox] [run <file> [args...] | check <file> | test [paths...] | fmt [--check] <files...> | highlight <file> | lsp | <file>]"
+            );
+            process::exit(2);
+        }
+    }
 }
@@ -1,6 +1,7 @@
-use std::fmt;
+use std::{borrow::Cow, fmt};
 
 use crate::{
+    ast::Pattern,
     error::Position,
     functions::{Type, UserDefinedFunction},
     token::Token,
@@ -10,12 +11,27 @@ use crate::{
 pub enum Node {
     Statements(Vec<Node>, Type, Position),
     Number(Token),
-    Call(Token, Vec<Node>, Type),
+    Str(Token),
+    Bool(Token),
+    // The resolved name, then the name as it was written, which call traces show.
+    Call(Token, Vec<Node>, Type, String),
     Define(Token, Box<Node>),
+    Let(Token, Box<Node>, Type, bool),
+    Assign(Token, Box<Node>),
+    While(Box<Node>, Box<Node>, Position),
+    For(Token, Box<Node>, Box<Node>, Box<Node>, Position),
+    Import(Token, Position),
     FuncAccess(Token, Vec<Type>, Type),
     Function(UserDefinedFunction, Position),
     Var(Token, Type),
-    If(Box<Node>, Box<Node>, Box<Node>, Position),
+    If(Box<Node>, Box<Node>, Box<Node>, Type, Position),
+    TypeDef(Token, Position),
+    Record(Token, Vec<(Token, Node)>, Position),
+    Field(Token, Box<Node>, Type),
+    Update(Box<Node>, Vec<(Token, Node)>, Type, Position),
+    Variant(Token, Vec<Node>, Type),
+    Match(Box<Node>, Vec<(Pattern, Node)>, Type, Position),
+    Try(Box<Node>, Option<Box<Node>>, Type, Position),
 }
 
 impl Node {
@@ -26,23 +42,45 @@ impl Node {
                 Box::new(p.ret().clone()),
             ),
             Node::Number(_) => Type::Number,
-            Node::Call(_, _, ret) => ret.clone(),
+            Node::Str(_) => Type::Str,
+            Node::Bool(_) => Type::Bool,
+            Node::Call(_, _, ret, _) => ret.clone(),
             Node::Statements(_, t, _) => t.clone(),
-            Node::Define(..) => Type::None,
+            Node::Define(..)
+            | Node::Let(..)
+            | Node::Assign(..)
+            | Node::While(..)
+            | Node::For(..)
+            | Node::Import(..) => Type::None,
             Node::Var(_, t) => t.clone(),
             Node::FuncAccess(_, p, r) => Type::Function(p.clone(), Box::new(r.clone())),
-            Node::If(_, then, _, _) => then.get_type(),
+            Node::If(.., t, _) => t.clone(),
+            Node::TypeDef(..) => Type::None,
+            Node::Record(t, ..) => Type::Named(Cow::Owned(t.to_string()), Cow::Borrowed(&[])),
+            Node::Field(.., t) => t.clone(),
+            Node::Update(.., t, _) | Node::Match(.., t, _) | Node::Try(.., t, _) => t.clone(),
+            Node::Variant(.., t) => t.clone(),
         }
     }
 
     pub fn position(&self) -> &Position {
         match self {
-            Node::Number(t) => t.position(),
-            Node::Call(t, _, _) => t.position(),
-            Node::Define(t, _) => t.position(),
+            Node::Number(t) | Node::Str(t) | Node::Bool(t) => t.position(),
+            Node::Call(t, ..) => t.position(),
+            Node::Define(t, _) | Node::Let(t, ..) | Node::Assign(t, _) => t.position(),
             Node::FuncAccess(t, _, _) => t.position(),
-            Node::If(.., pos) | Node::Function(_, pos) | Node::Statements(_, _, pos) => pos,
-            Node::Var(t, _) => t.position(),
+            Node::If(.., pos)
+            | Node::Function(_, pos)
+            | Node::Statements(_, _, pos)
+            | Node::TypeDef(_, pos)
+            | Node::Record(.., pos)
+            | Node::Update(.., pos)
+            | Node::Match(.., pos)
+            | Node::Try(.., pos)
+            | Node::While(.., pos)
+            | Node::For(.., pos)
+            | Node::Import(_, pos) => pos,
+            Node::Var(t, _) | Node::Field(t, ..) | Node::Variant(t, ..) => t.position(),
         }
     }
 }
@@ -51,7 +89,9 @@ impl fmt::Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Node::Number(t) => write!(f, "Number[{}]", t),
-            Node::Call(t, args, _) => write!(
+            Node::Str(t) => write!(f, "Str[{}]", t),
+            Node::Bool(t) => write!(f, "Bool[{}]", t),
+            Node::Call(t, args, ..) => write!(
                 f,
                 "Call[{}][{}]",
                 t,
@@ -70,6 +110,9 @@ impl fmt::Display for Node {
                     .join(";\n")
             ),
             Node::Define(t, n) => write!(f, "Define[{}][{}]", t, n),
+            Node::Let(t, n, ty, false) => write!(f, "Let[{}: {}][{}]", t, ty, n),
+            Node::Let(t, n, ty, true) => write!(f, "LetMut[{}: {}][{}]", t, ty, n),
+            Node::Assign(t, n) => write!(f, "Assign[{}][{}]", t, n),
             Node::Function(p, _) => write!(
                 f,
                 "Function[{}] ~> [{}] | {} |",
@@ -96,7 +139,54 @@ impl fmt::Display for Node {
                     .join(", "),
                 r,
             ),
-            Node::If(cond, then, else_, _) => write!(f, "If[{}][{}][{}]", cond, then, else_),
+            Node::If(cond, then, else_, ..) => write!(f, "If[{}][{}][{}]", cond, then, else_),
+            Node::While(cond, body, _) => write!(f, "While[{}][{}]", cond, body),
+            Node::For(t, start, end, body, _) => {
+                write!(f, "For[{}][{}][{}][{}]", t, start, end, body)
+            }
+            Node::TypeDef(t, _) => write!(f, "TypeDef[{}]", t),
+            Node::Import(t, _) => write!(f, "Import[{}]", t),
+            Node::Record(t, fields, _) => write!(
+                f,
+                "Record[{}][{}]",
+                t,
+                fields
+                    .iter()
+                    .map(|(t, n)| format!("{} : {}", t, n))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Node::Field(t, n, _) => write!(f, "Field[{}][{}]", t, n),
+            Node::Variant(t, args, _) => write!(
+                f,
+                "Variant[{}][{}]",
+                t,
+                args.iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Node::Match(n, arms, ..) => write!(
+                f,
+                "Match[{}][{}]",
+                n,
+                arms.iter()
+                    .map(|(p, n)| format!("{} ~> {}", p, n))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Node::Try(n, Some(fallback), ..) => write!(f, "Try[{}][{}]", n, fallback),
+            Node::Try(n, None, ..) => write!(f, "Try[{}]", n),
+            Node::Update(n, fields, ..) => write!(
+                f,
+                "Update[{}][{}]",
+                n,
+                fields
+                    .iter()
+                    .map(|(t, n)| format!("{} : {}", t, n))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
         }
     }
 }
@@ -1,12 +1,10 @@
 use crate::{
+    ast::{Expr, Pattern, TypeExpr},
     error::{Error, ErrorType},
-    functions::{Type, UserDefinedFunction},
-    node::Node,
-    scope::Scope,
     token::{Token, TokenType},
 };
 
-type ParseResult = Result<Node, Error>;
+type ParseResult = Result<Expr, Error>;
 
 struct Parser<'a> {
     tokens: &'a [Token],
@@ -15,7 +13,7 @@ struct Parser<'a> {
 }
 
 impl Parser<'_> {
-    fn new(tokens: &[Token]) -> Parser {
+    fn new(tokens: &[Token]) -> Parser<'_> {
         Parser {
             tokens,
             index: 0,
@@ -34,20 +32,25 @@ impl Parser<'_> {
         }
     }
 
-    fn make_type(&mut self) -> Result<Type, Error> {
+    fn make_type(&mut self) -> Result<TypeExpr, Error> {
         match *self.current {
-            TokenType::Keyword(ref s) => match s.as_str() {
-                "Int" => {
-                    self.advance();
-                    Ok(Type::Number)
+            TokenType::Keyword(_) => {
+                let t = TypeExpr::Named(self.current.clone(), vec![]);
+                self.advance();
+                Ok(t)
+            }
+            TokenType::Word(_) => {
+                let name = self.current.clone();
+                self.advance();
+                let mut args = vec![];
+                while *self.current == TokenType::LBracket {
+                    args.push(self.bracketed_type()?);
                 }
-                _ => Err(Error::new(
-                    ErrorType::SyntaxError,
-                    self.current.position().clone(),
-                    format!("Expected type, found {}", s),
-                )),
-            },
+                Ok(TypeExpr::Named(name, args))
+            }
+            TokenType::RBracket => Ok(TypeExpr::Hole(self.current.position().clone())),
             TokenType::LBracket => {
+                let mut s = self.current.position().clone();
                 let mut params = vec![];
                 while *self.current != TokenType::CurlyArrow {
                     if *self.current != TokenType::LBracket {
@@ -70,7 +73,9 @@ impl Parser<'_> {
                     params.push(t);
                 }
                 self.advance();
-                Ok(Type::Function(params, Box::new(self.make_type()?)))
+                let ret = self.make_type()?;
+                s.merge(ret.position());
+                Ok(TypeExpr::Function(params, Box::new(ret), s))
             }
             _ => Err(Error::new(
                 ErrorType::SyntaxError,
@@ -80,119 +85,176 @@ impl Parser<'_> {
         }
     }
 
-    fn statements(
-        &mut self,
-        scope: &mut Scope,
-        end_token: TokenType,
-    ) -> Result<(Vec<Node>, Type), Error> {
-        let mut new = scope.get_new();
+    fn bracketed_type(&mut self) -> Result<TypeExpr, Error> {
+        if *self.current != TokenType::LBracket {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                self.current.position().clone(),
+                format!("Expected '[', found '{}'", self.current),
+            ));
+        }
+        self.advance();
+        let type_ = self.make_type()?;
+        if *self.current != TokenType::RBracket {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                self.current.position().clone(),
+                "Expected ']' after type".to_string(),
+            ));
+        }
+        self.advance();
+        Ok(type_)
+    }
+
+    fn type_name(&mut self, kind: &str) -> Result<Token, Error> {
+        if !matches!(*self.current, TokenType::Word(ref s) if s.starts_with(char::is_uppercase)) {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                self.current.position().clone(),
+                format!("Expected {} name starting with an uppercase letter", kind),
+            ));
+        }
+        let name = self.current.clone();
+        self.advance();
+        Ok(name)
+    }
+
+    fn expect_lbrace(&mut self) -> Result<(), Error> {
+        if *self.current != TokenType::LBrace {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                self.current.position().clone(),
+                format!("Expected '{{', found '{}'", self.current),
+            ));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn statements(&mut self, end_token: TokenType) -> Result<Vec<Expr>, Error> {
         let mut statements = Vec::new();
-        let mut ret = Type::None;
         while *self.current != end_token {
-            let expr = self.expression(&mut new)?;
-            println!("{expr} {end_token:?}");
-            ret = expr.get_type();
-            statements.push(expr);
+            statements.push(self.expression()?);
         }
         self.advance();
-        scope.add(new);
-        Ok((statements, ret))
+        Ok(statements)
     }
 
-    fn expression(&mut self, scope: &mut Scope) -> ParseResult {
+    fn expression(&mut self) -> ParseResult {
         let token = self.current.clone();
         match *self.current {
             TokenType::Number(_) => {
                 self.advance();
-                Ok(Node::Number(token))
+                Ok(Expr::Number(token))
             }
-            TokenType::Word(_) => {
-                if matches!(self.peek(), Some(t) if **t == TokenType::Colon) {
-                    self.advance();
-                    self.advance();
-                    let node = self.expression(scope)?;
-                    scope.define((token.clone(), node.get_type()));
-                    Ok(Node::Define(token, Box::new(node)))
-                } else {
-                    let t = match scope.find(&self.current) {
-                        Some(t) => t,
-                        None => {
-                            return Err(Error::new(
-                                ErrorType::UndefinedFunction,
-                                self.current.position().clone(),
-                                format!("Undefined Function : {}", self.current),
-                            ))
-                        }
-                    };
-                    match t {
-                        Type::Function(params, ret) => {
-                            let mut args = Vec::new();
-                            self.advance();
-                            if *self.current == TokenType::Bang {
-                                self.advance();
-                                Ok(Node::FuncAccess(token, params, (*ret).clone()))
-                            } else {
-                                for ty in params {
-                                    let expr = self.expression(scope)?;
-                                    if expr.get_type() != ty {
-                                        return Err(Error::new(
-                                            ErrorType::TypeError,
-                                            self.current.position().clone(),
-                                            format!(
-                                                "Expected type {}, but got {}",
-                                                ty,
-                                                expr.get_type()
-                                            ),
-                                        ));
-                                    }
-                                    args.push(expr);
-                                }
-                                Ok(Node::Call(token, args, *ret))
-                            }
-                        }
-                        _ => {
-                            self.advance();
-                            Ok(Node::Var(token, t))
-                        }
+            TokenType::Str(_) => {
+                self.advance();
+                Ok(Expr::Str(token))
+            }
+            TokenType::Keyword(ref k) if k == "true" || k == "false" => {
+                self.advance();
+                Ok(Expr::Bool(token))
+            }
+            TokenType::Word(ref name) => {
+                let is_type = name.starts_with(char::is_uppercase);
+                self.advance();
+                match *self.current {
+                    TokenType::LBrace if is_type => {
+                        let mut s = token.position().clone();
+                        let fields = self.fields()?;
+                        s.merge(self.tokens[self.index - 1].position());
+                        Ok(Expr::Construct(token, fields, s))
+                    }
+                    TokenType::Colon => {
+                        self.advance();
+                        Ok(Expr::Define(token))
                     }
+                    TokenType::Bang => {
+                        self.advance();
+                        Ok(Expr::FuncAccess(token))
+                    }
+                    TokenType::LeftArrow => {
+                        self.advance();
+                        Ok(Expr::Assign(token))
+                    }
+                    _ => Ok(Expr::Word(token)),
                 }
             }
-            TokenType::LBracket => self.define_function(scope),
+            TokenType::LBracket => self.define_function(),
             TokenType::Pipe => {
                 let mut s = self.current.position().clone();
                 self.advance();
-                let (statements, ret) = self.statements(scope, TokenType::Pipe)?;
-                s.merge(self.current.position());
-                Ok(Node::Statements(statements, ret, s))
+                let statements = self.statements(TokenType::Pipe)?;
+                s.merge(self.tokens[self.index - 1].position());
+                Ok(Expr::Block(statements, s))
             }
             TokenType::Question => {
-                let mut s = self.current.position().clone();
                 self.advance();
-                let condition = self.expression(scope)?;
-                if condition.get_type() != Type::Bool {
+                Ok(Expr::If(token))
+            }
+            TokenType::Field(_) => {
+                self.advance();
+                Ok(Expr::Field(token))
+            }
+            TokenType::LBrace => {
+                let mut s = token.position().clone();
+                let fields = self.fields()?;
+                s.merge(self.tokens[self.index - 1].position());
+                Ok(Expr::Fields(fields, s))
+            }
+            TokenType::Keyword(ref k) if k == "with" => {
+                self.advance();
+                Ok(Expr::With(token))
+            }
+            TokenType::Keyword(ref k) if k == "let" => self.binding(),
+            TokenType::Keyword(ref k) if k == "import" => self.import(),
+            TokenType::Keyword(ref k) if k == "private" => {
+                self.advance();
+                Ok(Expr::Private(token))
+            }
+            TokenType::Keyword(ref k) if k == "test" => {
+                self.advance();
+                Ok(Expr::Test(token))
+            }
+            TokenType::Keyword(ref k) if k == "while" => {
+                let mut s = token.position().clone();
+                self.advance();
+                let condition = self.header()?;
+                let body = self.statements(TokenType::RBrace)?;
+                s.merge(self.tokens[self.index - 1].position());
+                Ok(Expr::While(condition, body, s))
+            }
+            TokenType::Keyword(ref k) if k == "for" => {
+                let mut s = token.position().clone();
+                self.advance();
+                if !matches!(*self.current, TokenType::Word(_))
+                    || !matches!(self.peek(), Some(t) if matches!(**t, TokenType::Keyword(ref k) if k == "in"))
+                {
                     return Err(Error::new(
-                        ErrorType::TypeError,
+                        ErrorType::SyntaxError,
                         self.current.position().clone(),
-                        "Expected bool".to_string(),
+                        "Expected a name followed by 'in' after for".to_string(),
                     ));
                 }
-                let then = self.expression(scope)?;
-                let else_ = self.expression(scope)?;
-                if then.get_type() != else_.get_type() {
-                    return Err(Error::new(
-                        ErrorType::TypeError,
-                        else_.position().clone(),
-                        format!("Branches of an if statement must have same types, expected {}, found {}", then.get_type(), else_.get_type()),
-                    ));
-                }
-                s.merge(self.current.position());
-                Ok(Node::If(
-                    Box::new(condition),
-                    Box::new(then),
-                    Box::new(else_),
-                    s,
-                ))
+                let var = self.current.clone();
+                self.advance();
+                self.advance();
+                let range = self.header()?;
+                let body = self.statements(TokenType::RBrace)?;
+                s.merge(self.tokens[self.index - 1].position());
+                Ok(Expr::For(var, range, body, s))
+            }
+            TokenType::Keyword(ref k) if k == "try" => {
+                self.advance();
+                Ok(Expr::Try(token))
             }
+            TokenType::Keyword(ref k) if k == "else" => {
+                self.advance();
+                Ok(Expr::Else(token))
+            }
+            TokenType::Keyword(ref k) if k == "record" => self.record(),
+            TokenType::Keyword(ref k) if k == "enum" => self.enumeration(),
+            TokenType::Keyword(ref k) if k == "match" => self.matching(),
             _ => Err(Error::new(
                 ErrorType::SyntaxError,
                 self.current.position().clone(),
@@ -201,7 +263,219 @@ impl Parser<'_> {
         }
     }
 
-    fn define_function(&mut self, scope: &mut Scope) -> ParseResult {
+    fn import(&mut self) -> ParseResult {
+        let mut s = self.current.position().clone();
+        self.advance();
+        if !matches!(*self.current, TokenType::Str(_)) {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                self.current.position().clone(),
+                "Expected a path after import".to_string(),
+            ));
+        }
+        let path = self.current.clone();
+        s.merge(path.position());
+        self.advance();
+        let mut name = None;
+        if matches!(*self.current, TokenType::Keyword(ref k) if k == "as") {
+            self.advance();
+            if !matches!(*self.current, TokenType::Word(_)) {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    self.current.position().clone(),
+                    "Expected a name after as".to_string(),
+                ));
+            }
+            s.merge(self.current.position());
+            name = Some(self.current.clone());
+            self.advance();
+        }
+        Ok(Expr::Import(path, name, s))
+    }
+
+    fn binding(&mut self) -> ParseResult {
+        self.advance();
+        let mutable = matches!(*self.current, TokenType::Keyword(ref k) if k == "mut");
+        if mutable {
+            self.advance();
+        }
+        let type_ = if *self.current == TokenType::LBracket {
+            Some(self.bracketed_type()?)
+        } else {
+            None
+        };
+        if !matches!(*self.current, TokenType::Word(_))
+            || !matches!(self.peek(), Some(t) if **t == TokenType::Colon)
+        {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                self.current.position().clone(),
+                "Expected a name followed by ':' after let".to_string(),
+            ));
+        }
+        let name = self.current.clone();
+        self.advance();
+        self.advance();
+        Ok(Expr::Let(name, type_, mutable))
+    }
+
+    fn fields(&mut self) -> Result<Vec<(Token, Vec<Expr>)>, Error> {
+        self.advance();
+        let mut fields = vec![];
+        while *self.current != TokenType::RBrace {
+            if !matches!(*self.current, TokenType::Word(_))
+                || !matches!(self.peek(), Some(t) if **t == TokenType::Colon)
+            {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    self.current.position().clone(),
+                    format!("Expected field name, found '{}'", self.current),
+                ));
+            }
+            let name = self.current.clone();
+            self.advance();
+            self.advance();
+            let mut value = vec![];
+            while *self.current != TokenType::RBrace
+                && !(matches!(*self.current, TokenType::Word(_))
+                    && matches!(self.peek(), Some(t) if **t == TokenType::Colon))
+            {
+                value.push(self.expression()?);
+            }
+            fields.push((name, value));
+        }
+        self.advance();
+        Ok(fields)
+    }
+
+    fn record(&mut self) -> ParseResult {
+        let mut s = self.current.position().clone();
+        self.advance();
+        let name = self.type_name("record")?;
+        self.expect_lbrace()?;
+        let mut fields = vec![];
+        while *self.current != TokenType::RBrace {
+            let type_ = self.bracketed_type()?;
+            if !matches!(*self.current, TokenType::Word(_)) {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    self.current.position().clone(),
+                    "Expected field name".to_string(),
+                ));
+            }
+            fields.push((type_, self.current.clone()));
+            self.advance();
+        }
+        s.merge(self.current.position());
+        self.advance();
+        Ok(Expr::Record(name, fields, s))
+    }
+
+    fn enumeration(&mut self) -> ParseResult {
+        let mut s = self.current.position().clone();
+        self.advance();
+        let name = self.type_name("enum")?;
+        let mut params = vec![];
+        while let TokenType::Word(_) = *self.current {
+            params.push(self.current.clone());
+            self.advance();
+        }
+        self.expect_lbrace()?;
+        let mut variants = vec![];
+        while *self.current != TokenType::RBrace {
+            let variant = self.type_name("variant")?;
+            let mut types = vec![];
+            while *self.current == TokenType::LBracket {
+                types.push(self.bracketed_type()?);
+            }
+            variants.push((variant, types));
+        }
+        s.merge(self.current.position());
+        self.advance();
+        Ok(Expr::Enum(name, params, variants, s))
+    }
+
+    // An arm starts with `_` or with a variant name and lowercase binders,
+    // followed by `~>`.
+    fn is_arm_start(&self) -> bool {
+        let word = |i: usize, f: fn(&str) -> bool| matches!(self.tokens.get(i).map(|t| &**t), Some(TokenType::Word(s)) if f(s));
+        let arrow = |i: usize| {
+            matches!(
+                self.tokens.get(i).map(|t| &**t),
+                Some(TokenType::CurlyArrow)
+            )
+        };
+        if word(self.index, |s| s == "_") {
+            return arrow(self.index + 1);
+        }
+        if !word(self.index, |s| s.starts_with(char::is_uppercase)) {
+            return false;
+        }
+        let mut i = self.index + 1;
+        while word(i, |s| s.starts_with(char::is_lowercase)) {
+            i += 1;
+        }
+        arrow(i)
+    }
+
+    fn pattern(&mut self) -> Pattern {
+        let token = self.current.clone();
+        self.advance();
+        if token.to_string() == "_" {
+            self.advance();
+            return Pattern::Wildcard(token);
+        }
+        let mut binders = vec![];
+        while *self.current != TokenType::CurlyArrow {
+            binders.push(self.current.clone());
+            self.advance();
+        }
+        self.advance();
+        Pattern::Variant(token, binders)
+    }
+
+    fn header(&mut self) -> Result<Vec<Expr>, Error> {
+        let mut exprs = vec![];
+        while *self.current != TokenType::LBrace {
+            if matches!(*self.current, TokenType::Word(_))
+                && matches!(self.peek(), Some(t) if **t == TokenType::LBrace)
+            {
+                exprs.push(Expr::Word(self.current.clone()));
+                self.advance();
+                break;
+            }
+            exprs.push(self.expression()?);
+        }
+        self.expect_lbrace()?;
+        Ok(exprs)
+    }
+
+    fn matching(&mut self) -> ParseResult {
+        let mut s = self.current.position().clone();
+        self.advance();
+        let target = self.header()?;
+        let mut arms = vec![];
+        while *self.current != TokenType::RBrace {
+            if !self.is_arm_start() {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    self.current.position().clone(),
+                    format!("Expected a pattern, found '{}'", self.current),
+                ));
+            }
+            let pattern = self.pattern();
+            let mut body = vec![];
+            while *self.current != TokenType::RBrace && !self.is_arm_start() {
+                body.push(self.expression()?);
+            }
+            arms.push((pattern, body));
+        }
+        s.merge(self.current.position());
+        self.advance();
+        Ok(Expr::Match(target, arms, s))
+    }
+
+    fn define_function(&mut self) -> ParseResult {
         let mut s = self.current.position().clone();
         let mut params = vec![];
         let mut ret = None;
@@ -274,41 +548,134 @@ impl Parser<'_> {
                 break;
             }
         }
-        let ret = match ret {
-            Some(t) => t,
-            None => {
-                return Err(Error::new(
-                    ErrorType::SyntaxError,
-                    self.current.position().clone(),
-                    "No return Type mentioned".to_string(),
-                ))
-            }
-        };
         self.advance();
-        for (t, p) in params.clone() {
-            scope.define((p, t));
-        }
-        let (stmts, ty) = self.statements(scope, TokenType::Pipe)?;
-        if ty != ret {
-            return Err(Error::new(
-                ErrorType::TypeError,
-                self.current.position().clone(),
-                format!("Return type mismatch, expected {}, found {}", ret, ty),
-            ));
-        }
-        s.merge(self.current.position());
-        Ok(Node::Function(
-            UserDefinedFunction::new(params, ret, stmts),
-            s,
-        ))
+        let body = self.statements(TokenType::Pipe)?;
+        s.merge(self.tokens[self.index - 1].position());
+        Ok(Expr::Function(params, ret, body, s))
     }
 }
 
 pub fn parse(tokens: &[Token]) -> ParseResult {
     let mut parser = Parser::new(tokens);
-    let mut scope = Scope::new();
     let mut s = parser.current.position().clone();
-    let (stmts, ty) = parser.statements(&mut scope, TokenType::Eof)?;
+    let statements = parser.statements(TokenType::Eof)?;
     s.merge(parser.current.position());
-    Ok(Node::Statements(stmts, ty, s))
+    Ok(Expr::Block(statements, s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer;
+
+    fn statements(source: &str) -> Vec<Expr> {
+        match parse(&lexer::lex(source, "test.tru".to_string()).unwrap()).unwrap() {
+            Expr::Block(statements, _) => statements,
+            _ => unreachable!(),
+        }
+    }
+
+    fn error(source: &str) -> Error {
+        parse(&lexer::lex(source, "test.tru".to_string()).unwrap()).unwrap_err()
+    }
+
+    fn shown(exprs: &[Expr]) -> Vec<String> {
+        exprs.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn applications_stay_flat() {
+        assert_eq!(
+            shown(&statements(". + 1 * 2 x")),
+            [".", "+", "1", "*", "2", "x"]
+        );
+    }
+
+    #[test]
+    fn function_definition() {
+        let exprs = statements("double : [Int] n ~> [Int] | * n 2 |");
+        assert!(matches!(&exprs[0], Expr::Define(t) if **t == "double"));
+        match &exprs[1] {
+            Expr::Function(params, Some(ret), body, _) => {
+                assert_eq!(params.len(), 1);
+                assert_eq!(params[0].0.to_string(), "Int");
+                assert!(*params[0].1 == "n");
+                assert_eq!(ret.to_string(), "Int");
+                assert_eq!(shown(body), ["*", "n", "2"]);
+            }
+            expr => panic!("expected a function, got {}", expr),
+        }
+    }
+
+    #[test]
+    fn function_without_parameters() {
+        let exprs = statements("one : [Int] | 1 |");
+        match &exprs[1] {
+            Expr::Function(params, Some(ret), body, _) => {
+                assert!(params.is_empty());
+                assert_eq!(ret.to_string(), "Int");
+                assert_eq!(shown(body), ["1"]);
+            }
+            expr => panic!("expected a function, got {}", expr),
+        }
+    }
+
+    #[test]
+    fn function_type_parameter() {
+        let exprs = statements("twice : [[Int] ~> Int] f [Int] x ~> [Int] | f f x |");
+        match &exprs[1] {
+            Expr::Function(params, ..) => {
+                assert!(matches!(params[0].0, TypeExpr::Function(..)));
+                assert_eq!(params[0].0.to_string(), "[Int] ~> Int");
+            }
+            expr => panic!("expected a function, got {}", expr),
+        }
+    }
+
+    #[test]
+    fn match_arms() {
+        let exprs = statements("match o {\n  Some x ~> x\n  None ~> 0\n}");
+        match &exprs[0] {
+            Expr::Match(target, arms, _) => {
+                assert_eq!(shown(target), ["o"]);
+                let arms = arms
+                    .iter()
+                    .map(|(pattern, body)| (pattern.to_string(), shown(body)))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    arms,
+                    [
+                        ("Some x".to_string(), vec!["x".to_string()]),
+                        ("None".to_string(), vec!["0".to_string()]),
+                    ]
+                );
+            }
+            expr => panic!("expected a match, got {}", expr),
+        }
+    }
+
+    #[test]
+    fn loops() {
+        let exprs = statements("for k in 1 6 { . k }\nwhile b { b <- false }");
+        assert!(matches!(&exprs[0], Expr::For(var, range, body, _)
+            if **var == "k" && shown(range) == ["1", "6"] && shown(body) == [".", "k"]));
+        assert!(matches!(&exprs[1], Expr::While(condition, body, _)
+            if shown(condition) == ["b"] && shown(body) == ["b <-", "false"]));
+    }
+
+    #[test]
+    fn missing_bracket_in_signature() {
+        assert_eq!(
+            error("f : [Int] x ~> Int | x |").to_string(),
+            "SyntaxError at 1:16 to 1:19 in test.tru ~> Expected '[', found 'Int'"
+        );
+    }
+
+    #[test]
+    fn missing_parameter_name() {
+        assert_eq!(
+            error("f : [Int] x [Int] ~> [Int] | x |").to_string(),
+            "SyntaxError at 1:19 to 1:21 in test.tru ~> Expected parameter name"
+        );
+    }
 }
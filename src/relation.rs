@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use crate::functions::Type;
+
+#[derive(Debug, Clone, Default)]
+pub struct Substitution {
+    solved: HashMap<usize, Type>,
+    next: usize,
+}
+
+impl Substitution {
+    pub fn new() -> Substitution {
+        Substitution::default()
+    }
+
+    pub fn fresh(&mut self) -> Type {
+        self.next += 1;
+        Type::Unknown(self.next)
+    }
+
+    pub fn instantiate(&mut self, ty: &Type) -> Type {
+        let mut vars = HashMap::new();
+        self.instantiate_with(ty, &mut vars)
+    }
+
+    fn instantiate_with(&mut self, ty: &Type, vars: &mut HashMap<String, Type>) -> Type {
+        match ty {
+            Type::Var(name) => match vars.get(&**name) {
+                Some(t) => t.clone(),
+                None => {
+                    let t = self.fresh();
+                    vars.insert(name.to_string(), t.clone());
+                    t
+                }
+            },
+            Type::Function(params, ret) => Type::Function(
+                params
+                    .iter()
+                    .map(|p| self.instantiate_with(p, vars))
+                    .collect(),
+                Box::new(self.instantiate_with(ret, vars)),
+            ),
+            Type::Named(name, args) => Type::Named(
+                name.clone(),
+                args.iter()
+                    .map(|a| self.instantiate_with(a, vars))
+                    .collect(),
+            ),
+            _ => ty.clone(),
+        }
+    }
+
+    pub fn apply(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Unknown(n) => match self.solved.get(n) {
+                Some(t) => self.apply(t),
+                None => ty.clone(),
+            },
+            Type::Function(params, ret) => Type::Function(
+                params.iter().map(|p| self.apply(p)).collect(),
+                Box::new(self.apply(ret)),
+            ),
+            Type::Named(name, args) => {
+                Type::Named(name.clone(), args.iter().map(|a| self.apply(a)).collect())
+            }
+            _ => ty.clone(),
+        }
+    }
+
+    // `a` and `b` must be the same type. Unknowns are solved as needed.
+    pub fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        match (self.apply(a), self.apply(b)) {
+            (Type::Unknown(a), Type::Unknown(b)) if a == b => true,
+            (Type::Unknown(n), t) | (t, Type::Unknown(n)) => self.solve(n, t),
+            (Type::Function(a, b), Type::Function(c, d)) => {
+                a.len() == c.len()
+                    && a.iter().zip(c.iter()).all(|(a, c)| self.unify(a, c))
+                    && self.unify(&b, &d)
+            }
+            (Type::Named(a, b), Type::Named(c, d)) => {
+                a == c
+                    && b.len() == d.len()
+                    && b.iter().zip(d.iter()).all(|(b, d)| self.unify(b, d))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    // A value of type `source` can be used where `target` is expected.
+    // `Any` accepts every type, but is itself only assignable to `Any`.
+    pub fn is_assignable(&mut self, target: &Type, source: &Type) -> bool {
+        match (self.apply(target), self.apply(source)) {
+            (Type::Any, _) => true,
+            (Type::Function(a, b), Type::Function(c, d)) => {
+                a.len() == c.len()
+                    && a.iter()
+                        .zip(c.iter())
+                        .all(|(a, c)| self.is_assignable(c, a))
+                    && self.is_assignable(&b, &d)
+            }
+            (a, b) => self.unify(&a, &b),
+        }
+    }
+
+    // The most precise type both `a` and `b` can be used as, if there is one.
+    pub fn join(&mut self, a: &Type, b: &Type) -> Option<Type> {
+        match (self.apply(a), self.apply(b)) {
+            (Type::Any, _) | (_, Type::Any) => Some(Type::Any),
+            (a, b) if self.unify(&a, &b) => Some(self.apply(&a)),
+            _ => None,
+        }
+    }
+
+    fn solve(&mut self, n: usize, t: Type) -> bool {
+        if t.contains_unknown(n) {
+            return false;
+        }
+        self.solved.insert(n, t);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+
+    fn function(params: &[Type], ret: Type) -> Type {
+        Type::Function(params.to_vec(), Box::new(ret))
+    }
+
+    #[test]
+    fn anything_is_assignable_to_any() {
+        let mut subst = Substitution::new();
+        assert!(subst.is_assignable(&Type::Any, &Type::Number));
+        assert!(subst.is_assignable(&Type::Any, &Type::Bool));
+        assert!(subst.is_assignable(&Type::Any, &Type::Var(Cow::Borrowed("T"))));
+        assert!(subst.is_assignable(&Type::Any, &function(&[Type::Number], Type::Bool)));
+        assert!(subst.is_assignable(&Type::Any, &Type::Any));
+    }
+
+    #[test]
+    fn any_is_not_assignable_to_other_types() {
+        let mut subst = Substitution::new();
+        assert!(!subst.is_assignable(&Type::Number, &Type::Any));
+        assert!(!subst.is_assignable(&Type::Var(Cow::Borrowed("T")), &Type::Any));
+        assert!(!subst.is_assignable(&function(&[], Type::Number), &Type::Any));
+    }
+
+    #[test]
+    fn any_does_not_unify_with_other_types() {
+        let mut subst = Substitution::new();
+        assert!(subst.unify(&Type::Any, &Type::Any));
+        assert!(!subst.unify(&Type::Any, &Type::Number));
+        assert!(!subst.unify(&Type::Number, &Type::Any));
+    }
+
+    #[test]
+    fn unknowns_are_solved_with_any() {
+        let mut subst = Substitution::new();
+        let t = subst.fresh();
+        assert!(subst.is_assignable(&t, &Type::Any));
+        assert_eq!(subst.apply(&t), Type::Any);
+        assert!(!subst.is_assignable(&Type::Number, &t));
+    }
+
+    #[test]
+    fn assigning_to_any_does_not_solve_unknowns() {
+        let mut subst = Substitution::new();
+        let t = subst.fresh();
+        assert!(subst.is_assignable(&Type::Any, &t));
+        assert!(subst.is_assignable(&t, &Type::Number));
+        assert_eq!(subst.apply(&t), Type::Number);
+    }
+
+    #[test]
+    fn function_parameters_are_contravariant() {
+        let mut subst = Substitution::new();
+        let takes_any = function(&[Type::Any], Type::Number);
+        let takes_int = function(&[Type::Number], Type::Number);
+        assert!(subst.is_assignable(&takes_int, &takes_any));
+        assert!(!subst.is_assignable(&takes_any, &takes_int));
+    }
+
+    #[test]
+    fn function_returns_are_covariant() {
+        let mut subst = Substitution::new();
+        let returns_any = function(&[], Type::Any);
+        let returns_int = function(&[], Type::Number);
+        assert!(subst.is_assignable(&returns_any, &returns_int));
+        assert!(!subst.is_assignable(&returns_int, &returns_any));
+    }
+
+    #[test]
+    fn type_variables_are_rigid() {
+        let mut subst = Substitution::new();
+        let t = Type::Var(Cow::Borrowed("T"));
+        assert!(subst.unify(&t, &Type::Var(Cow::Borrowed("T"))));
+        assert!(!subst.unify(&t, &Type::Var(Cow::Borrowed("U"))));
+        assert!(!subst.is_assignable(&t, &Type::Number));
+        assert!(!subst.is_assignable(&Type::Number, &t));
+    }
+
+    #[test]
+    fn join_only_widens_to_any_when_a_side_is_any() {
+        let mut subst = Substitution::new();
+        assert_eq!(subst.join(&Type::Number, &Type::Number), Some(Type::Number));
+        assert_eq!(subst.join(&Type::Number, &Type::Any), Some(Type::Any));
+        assert_eq!(subst.join(&Type::Any, &Type::Bool), Some(Type::Any));
+        assert_eq!(subst.join(&Type::Number, &Type::Bool), None);
+    }
+
+    #[test]
+    fn join_solves_unknowns() {
+        let mut subst = Substitution::new();
+        let t = subst.fresh();
+        assert_eq!(subst.join(&t, &Type::Bool), Some(Type::Bool));
+        assert_eq!(subst.apply(&t), Type::Bool);
+    }
+
+    #[test]
+    fn equality_is_structural() {
+        assert_ne!(Type::Any, Type::Number);
+        assert_ne!(Type::Number, Type::Any);
+        assert_eq!(
+            function(&[Type::Number], Type::Bool),
+            function(&[Type::Number], Type::Bool)
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    ast::Expr,
+    checker,
+    config::{Capability, Config},
+    error::Error,
+    interpreter::{self, Interpreter},
+    lexer,
+    loader::Loader,
+    node::Node,
+    parser,
+    relation::Substitution,
+    scope::Scope,
+    token::Token,
+    value::Value,
+};
+
+pub struct Repl {
+    scope: Scope,
+    subst: Substitution,
+    vars: HashMap<Token, Value>,
+    loader: Loader,
+    evaluated: usize,
+    interpreter: Interpreter,
+    capabilities: Vec<Capability>,
+    file: Arc<String>,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl::with_config(&Config::new())
+    }
+
+    pub fn with_config(config: &Config) -> Repl {
+        let (scope, subst, vars) = crate::environment(config);
+        Repl {
+            loader: Loader::new(scope.clone()),
+            evaluated: 0,
+            interpreter: Interpreter::new(config),
+            capabilities: config.capabilities.clone(),
+            scope,
+            subst,
+            vars,
+            file: Arc::new("<repl>".to_string()),
+        }
+    }
+
+    pub fn eval(&mut self, line: &str) -> Result<Vec<String>, Error> {
+        let tokens = lexer::lex(line, self.file.to_string())?;
+        let mut scope = self.scope.clone();
+        let mut vars = self.vars.clone();
+        let statements = match parser::parse(&tokens)? {
+            Expr::Block(exprs, pos) => checker::check_in(
+                &exprs,
+                &pos,
+                &mut scope,
+                &mut self.subst,
+                &mut self.loader,
+                &self.capabilities,
+            )?,
+            _ => unreachable!(),
+        };
+        self.interpreter.reset();
+        for module in &self.loader.modules()[self.evaluated..] {
+            self.interpreter.execute(module.statements(), &mut vars)?;
+        }
+        interpreter::hoist(&statements, &mut vars);
+        let mut output = Vec::new();
+        for statement in &statements {
+            let value = self.interpreter.evaluate(statement, &mut vars)?;
+            match statement {
+                Node::Define(t, node) => {
+                    output.push(format!("{} : {}", t, self.subst.apply(&node.get_type())))
+                }
+                Node::Let(t, _, ty, mutable) => output.push(format!(
+                    "let {}{} : {}",
+                    if *mutable { "mut " } else { "" },
+                    t,
+                    self.subst.apply(ty)
+                )),
+                _ if value != Value::None => output.push(value.to_string()),
+                _ => {}
+            }
+        }
+        self.scope = scope;
+        self.vars = vars;
+        self.evaluated = self.loader.modules().len();
+        Ok(output)
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
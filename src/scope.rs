@@ -1,40 +1,318 @@
-use crate::{functions::Type, token::Token, DEFINED_WORDS};
+use std::{cell::Cell, rc::Rc};
+
+use crate::{
+    functions::{BuiltInFunction, Type, TypeDef},
+    token::Token,
+    DEFINED_WORDS,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    Monomorphic,
+    Generic,
+    Mutable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    name: Token,
+    ty: Type,
+    binding: Binding,
+    runtime: Option<String>,
+    // The namespace that owns a private definition, which is `None` for the
+    // entry file and the REPL.
+    private: Option<Option<String>>,
+    declared: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Export {
+    pub name: String,
+    pub runtime: String,
+    pub ty: Type,
+    pub generic: bool,
+    pub mutable: bool,
+    pub private: bool,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Scope {
-    defined: Vec<(Token, Type)>,
+    defined: Vec<Entry>,
+    types: Vec<(Token, TypeDef)>,
     scopes: Vec<Scope>,
+    namespace: Option<String>,
+    top: bool,
+    tests: Vec<Token>,
+    // Shared by every scope of a program, to number renamed bindings.
+    renamed: Rc<Cell<usize>>,
 }
 
 impl Scope {
     pub fn new() -> Scope {
         Scope {
             defined: Vec::new(),
+            types: Vec::new(),
             scopes: Vec::new(),
+            namespace: None,
+            top: true,
+            tests: Vec::new(),
+            renamed: Rc::new(Cell::new(0)),
         }
     }
 
     pub fn get_new(&self) -> Scope {
         let mut new = Self::new();
         new.defined = self.defined.clone();
+        new.types = self.types.clone();
+        new.namespace = self.namespace.clone();
+        new.renamed = Rc::clone(&self.renamed);
+        new.top = false;
         new
     }
 
-    pub fn add(&mut self, scope: Self) {
+    pub fn add(&mut self, mut scope: Self) {
+        self.tests.append(&mut scope.tests);
         self.scopes.push(scope);
     }
 
+    fn push(&mut self, name: Token, ty: Type, binding: Binding, runtime: Option<String>) {
+        self.defined.push(Entry {
+            name,
+            ty,
+            binding,
+            runtime,
+            private: None,
+            declared: false,
+        });
+    }
+
     pub fn define(&mut self, node: (Token, Type)) {
-        self.defined.push(node);
+        let runtime = self.runtime(&node.0);
+        self.push(node.0, node.1, Binding::Monomorphic, runtime);
+    }
+
+    pub fn define_generic(&mut self, node: (Token, Type), runtime: Option<String>) {
+        self.push(node.0, node.1, Binding::Generic, runtime);
+    }
+
+    // A function that can be referenced before its definition.
+    pub fn declare(&mut self, node: (Token, Type)) {
+        let runtime = self.runtime(&node.0);
+        self.push(node.0, node.1, Binding::Generic, runtime);
+        if let Some(entry) = self.defined.last_mut() {
+            entry.declared = true;
+        }
+    }
+
+    pub fn define_mutable(&mut self, node: (Token, Type)) {
+        let runtime = self.runtime(&node.0);
+        self.push(node.0, node.1, Binding::Mutable, runtime);
+    }
+
+    pub fn define_alias(&mut self, node: (Token, Type), runtime: Option<String>) {
+        self.push(node.0, node.1, Binding::Monomorphic, runtime);
+    }
+
+    pub fn import(&mut self, name: Token, export: &Export, module: &str) {
+        let binding = if export.generic {
+            Binding::Generic
+        } else if export.mutable {
+            Binding::Mutable
+        } else {
+            Binding::Monomorphic
+        };
+        self.defined.push(Entry {
+            name,
+            ty: export.ty.clone(),
+            binding,
+            runtime: Some(export.runtime.clone()),
+            private: export.private.then(|| Some(module.to_string())),
+            declared: false,
+        });
+    }
+
+    pub fn make_private(&mut self, name: &Token) {
+        let namespace = self.namespace.clone();
+        if let Some(entry) = self.defined.iter_mut().rev().find(|e| e.name == *name) {
+            entry.private = Some(namespace);
+        }
+    }
+
+    pub fn add_test(&mut self, name: Token) {
+        self.tests.push(name);
+    }
+
+    // Every name that can be used here, without the private ones of other
+    // modules.
+    pub fn names(&self) -> Vec<(&Token, &Type)> {
+        self.defined
+            .iter()
+            .filter(|e| {
+                e.private
+                    .as_ref()
+                    .is_none_or(|owner| *owner == self.namespace)
+            })
+            .map(|e| (&e.name, &e.ty))
+            .collect()
+    }
+
+    pub fn tests(&self) -> &[Token] {
+        &self.tests
+    }
+
+    pub fn set_namespace(&mut self, namespace: Option<String>) {
+        self.namespace = namespace;
+        self.top = true;
+    }
+
+    pub fn qualify(&self, name: &Token) -> Option<String> {
+        match &self.namespace {
+            Some(namespace) if self.top => Some(format!("{}.{}", namespace, name)),
+            _ => None,
+        }
+    }
+
+    // The name a new binding has at runtime. A binding that shadows another
+    // one gets a name of its own, so code checked against the older binding
+    // keeps seeing it. A definition that was declared ahead of its body keeps
+    // the name it was declared with.
+    pub fn runtime(&self, name: &Token) -> Option<String> {
+        match self.entry(name) {
+            Some(entry) if entry.declared && entry.name.position() == name.position() => {
+                entry.runtime.clone()
+            }
+            Some(_) => {
+                self.renamed.set(self.renamed.get() + 1);
+                let base = self.qualify(name).unwrap_or_else(|| name.to_string());
+                Some(format!("{}#{}", base, self.renamed.get()))
+            }
+            None => self.qualify(name),
+        }
+    }
+
+    fn entry(&self, token: &Token) -> Option<&Entry> {
+        self.defined.iter().rev().find(|e| e.name == *token)
+    }
+
+    pub fn resolve(&self, token: &Token) -> Token {
+        match self.entry(token) {
+            Some(Entry {
+                runtime: Some(runtime),
+                ..
+            }) => token.renamed(runtime.clone()),
+            _ => token.clone(),
+        }
+    }
+
+    pub fn private_owner(&self, token: &Token) -> Option<&str> {
+        match &self.entry(token)?.private {
+            Some(owner) if *owner != self.namespace => owner.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn exports(&self) -> Vec<Export> {
+        let prefix = match &self.namespace {
+            Some(namespace) => format!("{}.", namespace),
+            None => return vec![],
+        };
+        let mut exports: Vec<Export> = vec![];
+        for entry in self.defined.iter().rev() {
+            if let Some(runtime) = &entry.runtime {
+                if runtime.starts_with(&prefix)
+                    && exports.iter().all(|e| e.name != entry.name.to_string())
+                {
+                    exports.push(Export {
+                        name: entry.name.to_string(),
+                        runtime: runtime.clone(),
+                        ty: entry.ty.clone(),
+                        generic: entry.binding == Binding::Generic,
+                        mutable: entry.binding == Binding::Mutable,
+                        private: entry.private.is_some(),
+                    });
+                }
+            }
+        }
+        exports.reverse();
+        exports
+    }
+
+    pub fn types(&self) -> &[(Token, TypeDef)] {
+        &self.types
+    }
+
+    pub fn is_mutable(&self, token: &Token) -> bool {
+        matches!(
+            self.entry(token),
+            Some(Entry {
+                binding: Binding::Mutable,
+                ..
+            })
+        )
+    }
+
+    pub fn define_type(&mut self, name: Token, def: TypeDef) {
+        self.types.push((name, def));
+    }
+
+    pub fn find_type(&self, name: &str) -> Option<&TypeDef> {
+        self.find_type_token(name).map(|(_, def)| def)
+    }
+
+    pub fn find_type_token(&self, name: &str) -> Option<(&Token, &TypeDef)> {
+        self.types
+            .iter()
+            .rev()
+            .find(|(t, _)| **t == name)
+            .map(|(t, def)| (t, def))
+    }
+
+    pub fn records_with_field(&self, field: &str) -> Vec<(&Token, &[(Type, Token)])> {
+        self.types
+            .iter()
+            .filter_map(|(t, def)| match def {
+                TypeDef::Record(fields) if fields.iter().any(|(_, f)| **f == field) => {
+                    Some((t, &fields[..]))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn find_variant(&self, variant: &Token) -> Option<(&Token, &TypeDef)> {
+        self.types
+            .iter()
+            .rev()
+            .find(|(_, def)| match def {
+                TypeDef::Enum(_, variants) => variants.iter().any(|(v, _)| v == variant),
+                TypeDef::Record(_) => false,
+            })
+            .map(|(t, def)| (t, def))
+    }
+
+    pub fn monomorphic(&self) -> impl Iterator<Item = &Type> {
+        self.defined
+            .iter()
+            .filter(|e| e.binding != Binding::Generic)
+            .map(|e| &e.ty)
+    }
+
+    pub fn builtin(&self, token: &Token) -> Option<&'static BuiltInFunction> {
+        match self.entry(token) {
+            Some(_) => None,
+            None => DEFINED_WORDS.iter().find(|f| **token == f.name()),
+        }
     }
 
-    pub fn find(&self, token: &Token) -> Option<Type> {
-        match self.defined.iter().rev().find(|(f, ..)| f == token) {
-            Some((_, t)) => Some(t.clone()),
-            None => DEFINED_WORDS
-                .iter()
-                .find(|f| **token == f.name())
-                .map(|f| Type::Function(f.params().to_vec(), Box::new(f.ret().clone()))),
+    pub fn find(&self, token: &Token) -> Option<(Type, bool)> {
+        match self.entry(token) {
+            Some(e) => Some((e.ty.clone(), e.binding == Binding::Generic)),
+            None => DEFINED_WORDS.iter().find(|f| **token == f.name()).map(|f| {
+                (
+                    Type::Function(f.params().to_vec(), Box::new(f.ret().clone())),
+                    true,
+                )
+            }),
         }
     }
 }
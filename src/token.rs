@@ -2,7 +2,7 @@ use std::{
     borrow::Cow,
     fmt,
     hash::{Hash, Hasher},
-    rc::Rc,
+    sync::Arc,
 };
 
 use ordered_float::OrderedFloat;
@@ -12,15 +12,21 @@ use crate::error::Position;
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub enum TokenType {
     Number(OrderedFloat<f64>),
+    Str(String),
     Word(String),
     Keyword(String),
+    Field(String),
     Colon,
     Pipe,
     LBracket,
     RBracket,
+    LBrace,
+    RBrace,
     CurlyArrow,
+    LeftArrow,
     Bang,
     Question,
+    Comment(String),
     Eof,
 }
 
@@ -61,7 +67,7 @@ impl Token {
         line_end: usize,
         column: usize,
         column_end: usize,
-        file: Rc<String>,
+        file: Arc<String>,
     ) -> Token {
         Token {
             token_type,
@@ -72,8 +78,17 @@ impl Token {
     pub fn position(&self) -> &Position {
         &self.position
     }
+
+    pub fn renamed(&self, name: String) -> Token {
+        Token {
+            token_type: TokenType::Word(name),
+            position: self.position.clone(),
+        }
+    }
 }
 
+// Bindings renamed by the checker are shown by the name they have in the
+// source, which is the part before the `#`.
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -81,15 +96,21 @@ impl fmt::Display for Token {
             "{}",
             match self.token_type {
                 TokenType::Number(n) => Cow::Owned(n.to_string()),
-                TokenType::Word(ref s) => Cow::Borrowed(&**s),
+                TokenType::Str(ref s) => Cow::Owned(format!("{:?}", s)),
+                TokenType::Word(ref s) => Cow::Borrowed(s.split('#').next().unwrap_or(s)),
                 TokenType::Keyword(ref s) => Cow::Borrowed(&**s),
+                TokenType::Field(ref s) => Cow::Owned(format!(".{}", s)),
                 TokenType::Colon => Cow::Borrowed(":"),
                 TokenType::Pipe => Cow::Borrowed("|"),
                 TokenType::LBracket => Cow::Borrowed("["),
                 TokenType::RBracket => Cow::Borrowed("]"),
+                TokenType::LBrace => Cow::Borrowed("{"),
+                TokenType::RBrace => Cow::Borrowed("}"),
                 TokenType::CurlyArrow => Cow::Borrowed("~>"),
+                TokenType::LeftArrow => Cow::Borrowed("<-"),
                 TokenType::Bang => Cow::Borrowed("!"),
                 TokenType::Question => Cow::Borrowed("?"),
+                TokenType::Comment(ref s) => Cow::Borrowed(&**s),
                 TokenType::Eof => Cow::Borrowed("EOF"),
             }
         )
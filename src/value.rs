@@ -1,18 +1,24 @@
-use std::fmt;
+use std::{cell::RefCell, fmt, mem, rc::Rc};
 
 use ordered_float::OrderedFloat;
 
 use crate::{
     functions::UserDefinedFunction,
+    interpreter::Frame,
     token::{Token, TokenType},
 };
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Number(OrderedFloat<f64>),
+    Str(String),
     Function(UserDefinedFunction),
+    Closure(UserDefinedFunction, Option<Rc<Frame>>),
     FuncAccess(Token),
     Bool(bool),
+    Record(String, Vec<(String, Value)>),
+    Variant(String, Vec<Value>),
+    Mutable(Rc<RefCell<Value>>),
     None,
 }
 
@@ -20,10 +26,30 @@ impl Value {
     pub fn from_token(token: &Token) -> Value {
         match **token {
             TokenType::Number(n) => Value::Number(n),
+            TokenType::Str(ref s) => Value::Str(s.clone()),
+            TokenType::Keyword(ref k) if k == "true" => Value::Bool(true),
+            TokenType::Keyword(ref k) if k == "false" => Value::Bool(false),
             _ => panic!("Invalid token type for value"),
         }
     }
 
+    // Bytes taken by this value itself, not counting the values it holds.
+    pub fn size(&self) -> usize {
+        mem::size_of::<Value>()
+            + match self {
+                Value::Str(s) => s.len(),
+                Value::Record(name, fields) => {
+                    name.len()
+                        + fields
+                            .iter()
+                            .map(|(field, _)| field.len() + mem::size_of::<(String, Value)>())
+                            .sum::<usize>()
+                }
+                Value::Variant(name, values) => name.len() + values.len() * mem::size_of::<Value>(),
+                _ => 0,
+            }
+    }
+
     pub fn get_number(&self) -> OrderedFloat<f64> {
         match self {
             Value::Number(n) => *n,
@@ -48,9 +74,25 @@ impl fmt::Display for Value {
         match self {
             Value::Bool(b) => write!(f, "{}", b),
             Value::Number(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{}", s),
             Value::None => write!(f, "()"),
-            Value::Function(func) => write!(f, "{}", func),
+            Value::Function(func) | Value::Closure(func, _) => write!(f, "{}", func),
             Value::FuncAccess(func) => write!(f, "{}", func),
+            Value::Mutable(value) => write!(f, "{}", value.borrow()),
+            Value::Record(name, fields) => {
+                write!(f, "{} {{", name)?;
+                for (field, value) in fields {
+                    write!(f, " {} : {}", field, value)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Variant(name, values) => {
+                write!(f, "{}", name)?;
+                for value in values {
+                    write!(f, " {}", value)?;
+                }
+                Ok(())
+            }
         }
     }
 }
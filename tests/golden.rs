@@ -0,0 +1,96 @@
+// Runs every program in tests/golden and compares what it prints, and the
+// error it stops with, against the .out and .err files next to it. Run with
+// BLESS=1 to write the current results as the new expectations. The programs
+// are also kept formatted. What a host configures, like limits, cancellation
+// and output, is tested in tests/host.rs.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+fn programs() -> Vec<PathBuf> {
+    let mut programs = fs::read_dir("tests/golden")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "tru"))
+        .collect::<Vec<_>>();
+    programs.sort();
+    programs
+}
+
+fn run(path: &Path) -> (String, String) {
+    let file = path.to_string_lossy().into_owned();
+    let source = fs::read_to_string(path).unwrap();
+    let output = Arc::new(Mutex::new(String::new()));
+    let sink = Arc::clone(&output);
+    let mut config = trulang::Config::new();
+    config.output = trulang::Output::callback(move |text| sink.lock().unwrap().push_str(text));
+    let result = trulang::run_with(&source, &file, &config).map_err(|err| diagnostic(&err));
+    let output = output.lock().unwrap().clone();
+    (output, result.err().unwrap_or_default())
+}
+
+fn diagnostic(err: &trulang::Error) -> String {
+    let root = format!("{}/", env!("CARGO_MANIFEST_DIR"));
+    format!("{}\n", err).replace(&root, "")
+}
+
+fn compare(path: &Path, actual: &str, bless: bool, failures: &mut Vec<String>) {
+    if bless {
+        if actual.is_empty() {
+            let _ = fs::remove_file(path);
+        } else {
+            fs::write(path, actual).unwrap();
+        }
+        return;
+    }
+    let expected = fs::read_to_string(path).unwrap_or_default();
+    if expected != actual {
+        failures.push(format!(
+            "{}\n--- expected\n{}--- actual\n{}",
+            path.display(),
+            expected,
+            actual
+        ));
+    }
+}
+
+#[test]
+fn golden() {
+    let bless = env::var_os("BLESS").is_some();
+    let mut failures = Vec::new();
+    for program in programs() {
+        let (output, diagnostic) = run(&program);
+        compare(
+            &program.with_extension("out"),
+            &output,
+            bless,
+            &mut failures,
+        );
+        compare(
+            &program.with_extension("err"),
+            &diagnostic,
+            bless,
+            &mut failures,
+        );
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\n{} golden files differ, run with BLESS=1 to update them",
+        failures.join("\n"),
+        failures.len()
+    );
+}
+
+#[test]
+fn formatted() {
+    for program in programs() {
+        let source = fs::read_to_string(&program).unwrap();
+        let file = program.to_string_lossy();
+        if let Ok(formatted) = trulang::format(&source, &file) {
+            assert_eq!(formatted, source, "{} is not formatted", file);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use trulang::{Config, SemanticKind::*};
+
+#[test]
+fn semantic_tokens() {
+    let source = "# doubles\ndouble : [Int] n ~> [Int] | * n 2 |\n. double 4\n";
+    let kinds = trulang::semantic_tokens(source, "highlight.tru", &Config::new())
+        .into_iter()
+        .map(|token| token.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            Comment,
+            Function,
+            Punctuation,
+            Punctuation,
+            TypeKeyword,
+            Punctuation,
+            Parameter,
+            Punctuation,
+            Punctuation,
+            TypeKeyword,
+            Punctuation,
+            Punctuation,
+            Builtin,
+            Parameter,
+            Literal,
+            Punctuation,
+            Builtin,
+            Function,
+            Literal,
+        ]
+    );
+}
+
+#[test]
+fn html() {
+    let html = trulang::highlight_html(". \"<b>\"", "highlight.tru", &Config::new());
+    assert_eq!(
+        html,
+        "<pre class=\"trulang\"><span class=\"builtin\">.</span> \
+         <span class=\"literal\">&quot;&lt;b&gt;&quot;</span></pre>\n"
+    );
+}
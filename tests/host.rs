@@ -0,0 +1,223 @@
+// What a host can do to a running script: bound its work, cancel it and
+// capture what it prints. These run on the test harness's default-sized
+// threads.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use trulang::{CancellationToken, Capability, Config, ErrorType, Limits, Output};
+
+const LOOP: &str = "let mut i : 0
+while true { i <- + i 1 }
+";
+
+const RECURSION: &str = "fib : [Int] n ~> [Int] | ? < n 2 n + fib - n 1 fib - n 2 |
+. fib 35
+";
+
+fn run(source: &str, limits: Limits) -> trulang::Error {
+    let mut config = Config::new();
+    config.limits = limits;
+    trulang::run_with(source, "limits.tru", &config).unwrap_err()
+}
+
+#[test]
+fn step_limit() {
+    let err = run(
+        LOOP,
+        Limits {
+            steps: Some(10_000),
+            ..Limits::unlimited()
+        },
+    );
+    assert_eq!(*err.error_type(), ErrorType::LimitError);
+    assert_eq!(
+        err.details(),
+        "Exceeded the limit of 10000 evaluation steps"
+    );
+}
+
+#[test]
+fn default_depth_limit() {
+    let source = "forever : [Int] n ~> [Int] | + 1 forever n |\n. forever 1\n";
+    let err = run(source, Limits::default());
+    assert_eq!(*err.error_type(), ErrorType::LimitError);
+    assert_eq!(err.details(), "Exceeded the maximum call depth of 1000");
+    assert_eq!(err.trace().len(), 1000);
+}
+
+#[test]
+fn deep_recursion_within_the_limit() {
+    let source = "count : [Int] n ~> [Int] | ? == n 0 0 + 1 count - n 1 |\n. count 20000\n";
+    let mut config = Config::new();
+    config.output = trulang::Output::discard();
+    config.limits = Limits {
+        depth: Some(20_001),
+        ..Limits::unlimited()
+    };
+    assert!(trulang::run_with(source, "limits.tru", &config).is_ok());
+}
+
+#[test]
+fn memory_limit() {
+    let source = "let mut s : \"\"\nwhile true { s <- concat s \"abcdefgh\" }\n";
+    let err = run(
+        source,
+        Limits {
+            memory: Some(1 << 16),
+            ..Limits::unlimited()
+        },
+    );
+    assert_eq!(*err.error_type(), ErrorType::LimitError);
+    assert_eq!(err.details(), "Exceeded the memory limit of 65536 bytes");
+    assert_eq!(err.position().line(), 2);
+}
+
+#[test]
+fn cancel_from_another_thread() {
+    let token = CancellationToken::new();
+    let mut config = Config::new();
+    config.cancellation = token.clone();
+    let script = thread::spawn(move || trulang::run_with(LOOP, "loop.tru", &config));
+    thread::sleep(Duration::from_millis(50));
+    token.cancel();
+    let err = script.join().unwrap().unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::Cancelled);
+    assert_eq!(err.position().line(), 2);
+}
+
+#[test]
+fn timeout_stops_a_loop() {
+    let mut config = Config::new();
+    config.cancellation = CancellationToken::with_timeout(Duration::from_millis(50));
+    let err = trulang::run_with(LOOP, "loop.tru", &config).unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::Cancelled);
+}
+
+#[test]
+fn timeout_stops_recursion_with_a_trace() {
+    let mut config = Config::new();
+    config.cancellation = CancellationToken::with_timeout(Duration::from_millis(500));
+    let err = trulang::run_with(RECURSION, "fib.tru", &config).unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::Cancelled);
+    assert!(!err.trace().is_empty());
+    assert!(err.trace().iter().all(|(name, _)| name == "fib"));
+    assert_eq!(err.trace().last().unwrap().1.line(), 2);
+}
+
+#[test]
+fn try_does_not_catch_cancellation() {
+    let source = "spin : [] | while true { } 0 |\n. try spin else 1\n";
+    let mut config = Config::new();
+    config.cancellation = CancellationToken::with_timeout(Duration::from_millis(50));
+    let err = trulang::run_with(source, "spin.tru", &config).unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::Cancelled);
+}
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Broken;
+
+impl Write for Broken {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn output_to_a_writer() {
+    let buffer = Buffer::default();
+    let mut config = Config::new();
+    config.output = Output::writer(buffer.clone());
+    trulang::run_with(". \"hi\"\nprint 1\n. + 1 2\n", "output.tru", &config).unwrap();
+    assert_eq!(*buffer.0.lock().unwrap(), b"hi\n13\n");
+}
+
+#[test]
+fn failing_writer() {
+    let mut config = Config::new();
+    config.output = Output::writer(Broken);
+    let err = trulang::run_with(". 1\n", "output.tru", &config).unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::IoError);
+    assert_eq!(err.position().line(), 1);
+}
+
+#[test]
+fn discarded_output() {
+    let mut config = Config::new();
+    config.output = Output::discard();
+    assert!(trulang::run_with(". 1\n", "output.tru", &config).is_ok());
+}
+
+#[test]
+fn printing_does_not_need_stdin() {
+    let buffer = Buffer::default();
+    let mut config = Config::sandboxed();
+    config.capabilities.push(Capability::Print);
+    config.output = Output::writer(buffer.clone());
+    trulang::run_with(". 1\n", "output.tru", &config).unwrap();
+    assert_eq!(*buffer.0.lock().unwrap(), b"1\n");
+    let err = trulang::run_with(". read_line\n", "output.tru", &config).unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::CapabilityError);
+    assert_eq!(
+        err.details(),
+        "read_line needs the stdin capability, which has not been granted"
+    );
+}
+
+#[test]
+fn sandboxed_imports_stay_in_the_project() {
+    let dir = env::temp_dir().join(format!("trulang-sandbox-{}", std::process::id()));
+    fs::create_dir_all(dir.join("project")).unwrap();
+    fs::write(dir.join("secret.txt"), "supersecret-token-123\n").unwrap();
+    fs::write(
+        dir.join("secret.tru"),
+        "token : [Str] | \"supersecret-token-123\" |\n",
+    )
+    .unwrap();
+    let main = dir.join("project/main.tru").display().to_string();
+    let outside = format!("import \"{}\"\n", dir.join("secret.txt").display());
+    let buffer = Buffer::default();
+    let mut config = Config::sandboxed();
+    config.capabilities.push(Capability::Print);
+    config.output = Output::writer(buffer.clone());
+    let run = |source: &str, config: &Config| trulang::run_with(source, &main, config);
+
+    let err = run("import \"../secret.tru\"\n. secret.token\n", &config).unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::CapabilityError);
+    assert!(!err.to_string().contains("supersecret"));
+    let err = run("import \"../secret.txt\"\n", &config).unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::ImportError);
+    assert_eq!(
+        err.details(),
+        "Only .tru files can be imported, not ../secret.txt"
+    );
+    let err = run(&outside, &config).unwrap_err();
+    assert_eq!(*err.error_type(), ErrorType::ImportError);
+    assert!(!err.to_string().contains("supersecret"));
+
+    config.capabilities.push(Capability::Filesystem);
+    run("import \"../secret.tru\"\n. secret.token\n", &config).unwrap();
+    assert_eq!(*buffer.0.lock().unwrap(), b"supersecret-token-123\n");
+    fs::remove_dir_all(dir).unwrap();
+}
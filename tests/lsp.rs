@@ -0,0 +1,197 @@
+use serde_json::{json, Value};
+
+const URI: &str = "file:///tmp/lsp_example.tru";
+const SOURCE: &str = "double : [Int] n ~> [Int] | * n 2 |
+. double 4
+";
+
+fn message(value: Value) -> String {
+    let body = value.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn request(id: u64, method: &str, line: u64, character: u64) -> String {
+    message(json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": {
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        },
+    }))
+}
+
+fn responses(output: &[u8]) -> Vec<Value> {
+    let mut output = std::str::from_utf8(output).unwrap();
+    let mut responses = Vec::new();
+    while let Some(start) = output.find("\r\n\r\n") {
+        let length = output[..start]
+            .trim_start_matches("Content-Length: ")
+            .parse::<usize>()
+            .unwrap();
+        let body = &output[start + 4..start + 4 + length];
+        responses.push(serde_json::from_str(body).unwrap());
+        output = &output[start + 4 + length..];
+    }
+    responses
+}
+
+#[test]
+fn language_server() {
+    let input = [
+        message(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} })),
+        message(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "text": SOURCE } },
+        })),
+        request(2, "textDocument/hover", 1, 3),
+        request(3, "textDocument/definition", 0, 30),
+        request(4, "textDocument/completion", 0, 30),
+        message(json!({
+            "jsonrpc": "2.0",
+            "id": 6,
+            "method": "textDocument/semanticTokens/full",
+            "params": { "textDocument": { "uri": URI } },
+        })),
+        message(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": { "uri": URI },
+                "contentChanges": [{ "text": ". + 1 \"two\"" }],
+            },
+        })),
+        message(json!({ "jsonrpc": "2.0", "id": 5, "method": "shutdown" })),
+        message(json!({ "jsonrpc": "2.0", "method": "exit" })),
+    ]
+    .concat();
+    let mut output = Vec::new();
+    trulang::serve_lsp(input.as_bytes(), &mut output, &trulang::Config::new()).unwrap();
+    let responses = responses(&output);
+
+    assert_eq!(
+        responses[0]["result"]["capabilities"]["hoverProvider"],
+        true
+    );
+    assert_eq!(responses[1]["params"]["diagnostics"], json!([]));
+
+    let hover = &responses[2]["result"];
+    assert_eq!(hover["contents"]["value"], "```\n[Int] ~> [Int]\n```");
+
+    let definition = &responses[3]["result"];
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 0, "character": 15 })
+    );
+
+    let completions = responses[4]["result"].as_array().unwrap();
+    let labels = completions
+        .iter()
+        .map(|c| c["label"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    for name in ["n", "double", "map", "sqrt", "match"] {
+        assert!(labels.contains(&name), "{} is not completed", name);
+    }
+
+    // `double` at the start of the first line is a function, and `n` in its
+    // body is a parameter.
+    let data = responses[5]["result"]["data"].as_array().unwrap();
+    assert_eq!(
+        data[..5],
+        [json!(0), json!(0), json!(6), json!(0), json!(0)]
+    );
+    assert_eq!(
+        data[60..65],
+        [json!(0), json!(2), json!(1), json!(1), json!(0)]
+    );
+
+    let diagnostics = &responses[6]["params"]["diagnostics"];
+    assert_eq!(diagnostics[0]["code"], "TypeError");
+    assert_eq!(diagnostics[0]["message"], "Expected type Int, but got Str");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 0, "character": 6 })
+    );
+    assert_eq!(responses[7]["id"], 5);
+}
+
+#[test]
+fn analysis_before_an_error() {
+    let source = format!("{}. + 1 \"two\"\n", SOURCE);
+    let analysis = trulang::analyze(&source, "lsp_example.tru", &trulang::Config::new());
+    assert_eq!(
+        analysis.error.as_ref().unwrap().details(),
+        "Expected type Int, but got Str"
+    );
+    let symbol = analysis.symbol_at(2, 3).unwrap();
+    assert_eq!(symbol.name, "double");
+    assert_eq!(symbol.kind, trulang::SymbolKind::Function);
+    assert_eq!(symbol.definition.as_ref().unwrap().line(), 1);
+    assert!(analysis
+        .completions(1, 29)
+        .iter()
+        .any(|c| c.name == "n" && c.kind == trulang::SymbolKind::Parameter));
+}
+
+// The emoji is two UTF-16 code units but one character, so `double` after it
+// starts at 15 in UTF-16 and at 14 in UTF-32.
+#[test]
+fn position_encodings() {
+    let text = "double : [Int] n ~> [Int] | * n 2 |\nlet s : \"😀\" . double 4\n";
+    for (encodings, character) in [(json!(["utf-16"]), 15), (json!(["utf-16", "utf-32"]), 14)] {
+        let input = [
+            message(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "capabilities": { "general": { "positionEncodings": encodings } } },
+            })),
+            message(json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": { "uri": URI, "text": text } },
+            })),
+            request(2, "textDocument/hover", 1, character),
+        ]
+        .concat();
+        let mut output = Vec::new();
+        trulang::serve_lsp(input.as_bytes(), &mut output, &trulang::Config::new()).unwrap();
+        let responses = responses(&output);
+        let encoding = if character == 15 { "utf-16" } else { "utf-32" };
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["positionEncoding"],
+            encoding
+        );
+        assert_eq!(
+            responses[2]["result"]["range"],
+            json!({
+                "start": { "line": 1, "character": character },
+                "end": { "line": 1, "character": character + 6 },
+            })
+        );
+    }
+}
+
+#[test]
+fn unparseable_messages() {
+    let input = [
+        "Content-Length: 5\r\n\r\n{oops".to_string(),
+        "Content-Type: application/json\r\n\r\n".to_string(),
+        message(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} })),
+    ]
+    .concat();
+    let mut output = Vec::new();
+    trulang::serve_lsp(input.as_bytes(), &mut output, &trulang::Config::new()).unwrap();
+    let responses = responses(&output);
+    assert_eq!(responses.len(), 3);
+    for response in &responses[..2] {
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], -32700);
+    }
+    assert_eq!(responses[1]["error"]["message"], "Missing Content-Length");
+    assert_eq!(responses[2]["id"], 1);
+    assert!(responses[2]["result"]["capabilities"].is_object());
+}